@@ -0,0 +1,351 @@
+//! Evaluation of a formula on many assignments at once.
+//!
+//! Each lane (bit) of a [`Word`] is a different assignment: variables are words and
+//! operators are bitwise operations, so a `u64` evaluates 64 rows of a truth table per pass.
+use std::ops::{BitAnd, BitOr, BitXor, Not};
+
+use crate::bool_formula_ast::{MyError, Node, Oper};
+use crate::bytecode::{Instr, Program};
+use crate::variable::Var;
+
+/// Most variables of a formula whose truth table is enumerated, so that its rows fit in a `u64`
+pub const MAX_VARIABLES: usize = 63;
+
+/// Fixed size set of lanes.
+pub trait Word:
+    Copy
+    + Eq
+    + BitAnd<Output = Self>
+    + BitOr<Output = Self>
+    + BitXor<Output = Self>
+    + Not<Output = Self>
+{
+    const LANES: u32;
+    const ZERO: Self;
+    const ONES: Self;
+
+    fn lane(&self, i: u32) -> bool;
+    fn set_lane(&mut self, i: u32);
+    fn count_ones(&self) -> u32;
+
+    /// Lanes whose index has bit `bit` set, e.g. `0b1010...` for `0`.
+    fn pattern(bit: u32) -> Self {
+        let mut word = Self::ZERO;
+        (0..Self::LANES)
+            .filter(|i| i >> bit & 1 == 1)
+            .for_each(|i| word.set_lane(i));
+        word
+    }
+}
+
+macro_rules! impl_word {
+    ($($int:ty),*) => {$(
+        impl Word for $int {
+            const LANES: u32 = <$int>::BITS;
+            const ZERO: Self = 0;
+            const ONES: Self = !0;
+
+            #[inline]
+            fn lane(&self, i: u32) -> bool {
+                self >> i & 1 == 1
+            }
+            #[inline]
+            fn set_lane(&mut self, i: u32) {
+                *self |= 1 << i;
+            }
+            #[inline]
+            fn count_ones(&self) -> u32 {
+                <$int>::count_ones(*self)
+            }
+        }
+    )*};
+}
+
+impl_word!(u64, u128);
+
+/// 256 lanes, lane `i` is bit `i % 64` of `self.0[i / 64]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct W256(pub [u64; 4]);
+
+macro_rules! impl_w256_op {
+    ($($trait:ident $method:ident $op:tt),*) => {$(
+        impl $trait for W256 {
+            type Output = Self;
+            #[inline]
+            fn $method(self, rhs: Self) -> Self {
+                Self(std::array::from_fn(|i| self.0[i] $op rhs.0[i]))
+            }
+        }
+    )*};
+}
+
+impl_w256_op!(BitAnd bitand &, BitOr bitor |, BitXor bitxor ^);
+
+impl Not for W256 {
+    type Output = Self;
+    #[inline]
+    fn not(self) -> Self {
+        Self(self.0.map(|w| !w))
+    }
+}
+
+impl Word for W256 {
+    const LANES: u32 = 256;
+    const ZERO: Self = Self([0; 4]);
+    const ONES: Self = Self([!0; 4]);
+
+    #[inline]
+    fn lane(&self, i: u32) -> bool {
+        self.0[i as usize / 64].lane(i % 64)
+    }
+    #[inline]
+    fn set_lane(&mut self, i: u32) {
+        self.0[i as usize / 64].set_lane(i % 64);
+    }
+    #[inline]
+    fn count_ones(&self) -> u32 {
+        self.0.iter().map(|w| w.count_ones()).sum()
+    }
+}
+
+/// Runs a [`Program`] on [`Word::LANES`] assignments at once.
+pub struct ParallelVm<'a, W> {
+    program: &'a Program,
+    stack: Vec<W>,
+    /// Words of the variables for the current block of rows
+    values: Vec<W>,
+    /// `W::pattern` of the bits of the row that vary inside a block
+    patterns: Vec<W>,
+    /// Lanes that are rows of the truth table, all of them unless it's smaller than a word
+    rows_mask: W,
+}
+
+impl<'a, W: Word> ParallelVm<'a, W> {
+    pub fn new(program: &'a Program) -> Self {
+        let nb_vars = program.variables().len() as u32;
+        let mut rows_mask = W::ZERO;
+        (0..W::LANES)
+            .filter(|&i| nb_vars >= 32 || i < 1 << nb_vars)
+            .for_each(|i| rows_mask.set_lane(i));
+        let lane_bits = W::LANES.trailing_zeros();
+        Self {
+            program,
+            stack: Vec::new(),
+            values: Vec::with_capacity(nb_vars as usize),
+            patterns: (0..lane_bits.min(nb_vars)).map(W::pattern).collect(),
+            rows_mask,
+        }
+    }
+
+    /// `values[i]` holds the values of the variable in slot `i`, one per lane.
+    pub fn evaluate(&mut self, values: &[W]) -> W {
+        assert_eq!(values.len(), self.program.variables().len());
+        let stack = &mut self.stack;
+        stack.clear();
+        for instr in self.program.code() {
+            match *instr {
+                Instr::Const(val) => stack.push(if val { W::ONES } else { W::ZERO }),
+                Instr::Load(slot) => stack.push(values[slot as usize]),
+                Instr::Not => {
+                    let top = stack.last_mut().unwrap();
+                    *top = !*top;
+                }
+                Instr::Apply(op, n) => {
+                    let first = stack.len() - n as usize;
+                    let operands = stack.drain(first..);
+                    let result = match op {
+                        Oper::Conjunction => operands.reduce(|acc, w| acc & w),
+                        Oper::Disjunction => operands.reduce(|acc, w| acc | w),
+                        Oper::ExclusiveDisjunction => operands.reduce(|acc, w| acc ^ w),
+                        Oper::MaterialCondition => operands.reduce(|left, right| !left | right),
+                        Oper::Equivalence => operands.reduce(|left, right| !(left ^ right)),
+                    };
+                    stack.push(result.unwrap());
+                }
+            }
+        }
+        stack.pop().unwrap()
+    }
+
+    /// Evaluates the rows `first_row..first_row + W::LANES` of the truth table,
+    /// row `first_row + i` in lane `i`. Lanes past the end of the table are cleared.
+    ///
+    /// `first_row` must be a multiple of `W::LANES`.
+    pub fn evaluate_rows(&mut self, first_row: u64) -> W {
+        debug_assert_eq!(first_row % W::LANES as u64, 0);
+        let nb_vars = self.program.variables().len() as u32;
+        let mut values = std::mem::take(&mut self.values);
+        values.clear();
+        // The first variable is the most significant bit of the row
+        values.extend((0..nb_vars).rev().map(|bit| {
+            if let Some(&pattern) = self.patterns.get(bit as usize) {
+                pattern
+            } else if first_row >> bit & 1 == 1 {
+                W::ONES
+            } else {
+                W::ZERO
+            }
+        }));
+        let result = self.evaluate(&values) & self.rows_mask;
+        self.values = values;
+        result
+    }
+
+    /// Number of rows of the truth table.
+    ///
+    /// # Panics
+    ///
+    /// With more than [`MAX_VARIABLES`] variables.
+    pub fn rows(&self) -> u64 {
+        let nb_vars = self.program.variables().len();
+        assert!(
+            nb_vars <= MAX_VARIABLES,
+            "too many variables to enumerate: {nb_vars}"
+        );
+        1 << nb_vars
+    }
+
+    /// Rows of the truth table, in blocks of `W::LANES`, see [`ParallelVm::rows`].
+    pub fn blocks(&mut self) -> impl Iterator<Item = W> + '_ {
+        (0..self.rows())
+            .step_by(W::LANES as usize)
+            .map(|first_row| self.evaluate_rows(first_row))
+    }
+}
+
+/// Whether some assignment satisfies `node`.
+///
+/// Panics with more than [`MAX_VARIABLES`] variables.
+pub fn satisfiable(node: &Node) -> bool {
+    let program = Program::compile(node);
+    ParallelVm::<u64>::new(&program)
+        .blocks()
+        .any(|block| block != 0)
+}
+
+/// Number of assignments of [`Node::variables`] that satisfy `node`.
+///
+/// Panics with more than [`MAX_VARIABLES`] variables.
+pub fn count_models(node: &Node) -> u64 {
+    let program = Program::compile(node);
+    ParallelVm::<u64>::new(&program)
+        .blocks()
+        .map(|block| block.count_ones() as u64)
+        .sum()
+}
+
+/// Whether `a` and `b` have the same value for every assignment of their variables.
+///
+/// Panics with more than [`MAX_VARIABLES`] variables.
+pub fn equivalent(a: &Node, b: &Node) -> bool {
+    let mut variables = a.variables();
+    variables.extend(b.variables());
+    variables.sort();
+    variables.dedup();
+    let compile =
+        |node| -> Result<Program, MyError> { Program::compile_with_vars(node, variables.clone()) };
+    let (a, b) = (compile(a).unwrap(), compile(b).unwrap());
+    let mut a = ParallelVm::<u64>::new(&a);
+    let mut b = ParallelVm::<u64>::new(&b);
+    a.blocks().zip(b.blocks()).all(|(a, b)| a == b)
+}
+
+/// Convenience to evaluate a formula over words given by variable.
+pub fn evaluate_words<W: Word>(node: &Node, word_of: impl Fn(Var) -> W) -> W {
+    let program = Program::compile(node);
+    let values = program
+        .variables()
+        .iter()
+        .map(|&var| word_of(var))
+        .collect::<Vec<_>>();
+    ParallelVm::new(&program).evaluate(&values)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::Vm;
+
+    #[test]
+    fn patterns() {
+        assert_eq!(u64::pattern(0), 0xaaaa_aaaa_aaaa_aaaa);
+        assert_eq!(u64::pattern(5), 0xffff_ffff_0000_0000);
+        assert_eq!(u128::pattern(6), !0 << 64);
+        assert_eq!(W256::pattern(7), W256([0, 0, !0, !0]));
+        assert_eq!(W256::pattern(1), W256([0xcccc_cccc_cccc_cccc; 4]));
+        assert!(W256::pattern(7).lane(200));
+        assert_eq!(W256::ONES.count_ones(), 256);
+    }
+
+    #[test]
+    fn evaluate_words() {
+        let tree = Node::parse("AB&C|").unwrap();
+        let word = super::evaluate_words(&tree, |var| match var.name() {
+            "A" => 0b1100u64,
+            "B" => 0b1010,
+            _ => 0b0001,
+        });
+        assert_eq!(word & 0b1111, 0b1001);
+    }
+
+    /// Same results as the scalar VM, whatever the word size
+    fn assert_matches_vm<W: Word>(tree: &Node) {
+        let program = Program::compile(tree);
+        let mut vm = Vm::new(&program);
+        let mut parallel = ParallelVm::<W>::new(&program);
+        let rows = parallel.rows();
+        let blocks = parallel.blocks().collect::<Vec<_>>();
+        assert_eq!(blocks.len() as u64, rows.div_ceil(W::LANES as u64));
+        for row in 0..rows {
+            let block = blocks[(row / W::LANES as u64) as usize];
+            assert_eq!(
+                block.lane((row % W::LANES as u64) as u32),
+                vm.evaluate_row(row)
+            );
+        }
+        // Lanes past the end of the table are cleared
+        let last = blocks.last().unwrap();
+        assert!((rows.min(W::LANES as u64) as u32..W::LANES).all(|i| !last.lane(i)));
+    }
+
+    #[test]
+    fn matches_vm() {
+        let vars = ["A", "B", "C", "D", "E", "F", "G", "H", "x1", "x2"];
+        for _ in 0..100 {
+            let mut tree =
+                Node::new_random(&vars.map(Var::new)[..rand::random::<usize>() % 10 + 1]);
+            tree.flatten_mut();
+            assert_matches_vm::<u64>(&tree);
+            assert_matches_vm::<u128>(&tree);
+            assert_matches_vm::<W256>(&tree);
+        }
+    }
+
+    #[test]
+    fn checks() {
+        let parse = |formula| Node::parse(formula).unwrap();
+        assert!(satisfiable(&parse("AB&")));
+        assert!(!satisfiable(&parse("AA!&")));
+        assert_eq!(count_models(&parse("AB|")), 3);
+        assert_eq!(count_models(&parse("1")), 1);
+        assert!(equivalent(&parse("AB&!"), &parse("A!B!|")));
+        assert!(equivalent(&parse("AB>"), &parse("A!B|C0&|")));
+        assert!(!equivalent(&parse("AB>"), &parse("BA>")));
+    }
+
+    #[test]
+    fn many_variables() {
+        // 2^22 rows, too slow one at a time in debug builds
+        let vars = (0..22).map(|i| format!("[x{i}]")).collect::<Vec<_>>();
+        let tree = Node::parse(format!("{}{}", vars.concat(), "^".repeat(21))).unwrap();
+        assert_eq!(count_models(&tree), 1 << 21);
+    }
+
+    #[test]
+    #[should_panic(expected = "too many variables to enumerate: 70")]
+    fn too_many_variables() {
+        let vars = (0..70).map(|i| format!("[x{i}]")).collect::<Vec<_>>();
+        let tree = Node::parse(format!("{}{}", vars.concat(), "|".repeat(69))).unwrap();
+        satisfiable(&tree);
+    }
+}
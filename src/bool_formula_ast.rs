@@ -1,21 +1,52 @@
+use std::cmp::Ordering;
 use std::default;
 use std::mem;
+use std::slice;
+use std::vec;
 
 use thiserror::Error;
 
+use crate::diagnostic::{Span, Spanned};
+use crate::variable::Var;
+
 #[derive(Error, Debug)]
 pub enum MyError {
     #[error("invalid character: '{0}'")]
-    InvalidChar(char),
+    InvalidChar(char, Span),
     #[error("invalid operator: '{0}'")]
     InvalidOperator(char),
     #[error("premature end of formula")]
-    Eof,
+    Eof(Span),
+    #[error("missing operand for '{0}'")]
+    MissingOperand(char, Span),
+    #[error("trailing operands: formula has more than one root")]
+    TrailingOperands(Span),
     #[error("value for variable '{0}' is not set")]
-    UnsetVariable(char),
+    UnsetVariable(Var),
+    #[error("invalid variable name: '{0}'")]
+    InvalidVariable(String, Span),
+    #[error("unexpected '{0}'")]
+    Unexpected(String, Span),
+    #[error("unclosed '{0}'")]
+    Unclosed(char, Span),
+}
+
+impl Spanned for MyError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::InvalidChar(_, span)
+            | Self::Eof(span)
+            | Self::MissingOperand(_, span)
+            | Self::TrailingOperands(span)
+            | Self::InvalidVariable(_, span)
+            | Self::Unexpected(_, span)
+            | Self::Unclosed(_, span) => Some(*span),
+            Self::InvalidOperator(_) | Self::UnsetVariable(_) => None,
+        }
+    }
 }
 
-#[derive(Debug, Clone, PartialEq, Copy)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Copy)]
 pub enum Oper {
     Conjunction,
     Disjunction,
@@ -44,53 +75,140 @@ impl Oper {
         }
     }
 
-    pub fn from_ascii(char: char) -> Result<Self, MyError> {
+    pub fn from_ascii(char: char) -> Option<Self> {
         match char {
-            '&' => Ok(Self::Conjunction),
-            '|' => Ok(Self::Disjunction),
-            '^' => Ok(Self::ExclusiveDisjunction),
-            '>' => Ok(Self::MaterialCondition),
-            '=' => Ok(Self::Equivalence),
-            _ => Err(MyError::InvalidChar(char)),
+            '&' => Some(Self::Conjunction),
+            '|' => Some(Self::Disjunction),
+            '^' => Some(Self::ExclusiveDisjunction),
+            '>' => Some(Self::MaterialCondition),
+            '=' => Some(Self::Equivalence),
+            _ => None,
+        }
+    }
+
+    pub fn from_utf8(char: char) -> Option<Self> {
+        match char {
+            '∧' => Some(Self::Conjunction),
+            '∨' => Some(Self::Disjunction),
+            '⊕' => Some(Self::ExclusiveDisjunction),
+            '⇒' => Some(Self::MaterialCondition),
+            '⇔' => Some(Self::Equivalence),
+            _ => None,
+        }
+    }
+
+    /// Binding strength in infix notation, higher binds tighter.
+    ///
+    /// `!` binds tighter than any binary operator.
+    pub fn precedence(&self) -> u8 {
+        match self {
+            Self::Conjunction => 5,
+            Self::ExclusiveDisjunction => 4,
+            Self::Disjunction => 3,
+            Self::MaterialCondition => 2,
+            Self::Equivalence => 1,
+        }
+    }
+
+    /// `A > B > C` reads as `A > (B > C)`, every other operator groups to the left.
+    pub fn is_right_associative(&self) -> bool {
+        matches!(self, Self::MaterialCondition)
+    }
+
+    /// `(A op B) op C` is equivalent to `A op (B op C)`.
+    pub fn is_associative(&self) -> bool {
+        !matches!(self, Self::MaterialCondition)
+    }
+
+    /// Whether an [`Op`] of this operator can have more than two operands.
+    ///
+    /// `=` is associative too, but `A = B = C` reading as a parity check is a trap, so it stays binary.
+    pub fn is_n_ary(&self) -> bool {
+        matches!(
+            self,
+            Self::Conjunction | Self::Disjunction | Self::ExclusiveDisjunction
+        )
+    }
+
+    /// Applies the operator to its operands.
+    ///
+    /// # Panics
+    ///
+    /// If `>` or `=` don't get exactly two operands.
+    pub fn apply<I: IntoIterator<Item = bool>>(&self, operands: I) -> bool {
+        let mut operands = operands.into_iter();
+        match self {
+            Self::Conjunction => operands.all(|val| val),
+            Self::Disjunction => operands.any(|val| val),
+            Self::ExclusiveDisjunction => operands.fold(false, |acc, val| acc != val),
+            Self::MaterialCondition | Self::Equivalence => {
+                let (Some(left), Some(right), None) =
+                    (operands.next(), operands.next(), operands.next())
+                else {
+                    panic!("'{}' takes two operands", self.ascii_char());
+                };
+                match self {
+                    Self::MaterialCondition => !left || right,
+                    _ => left == right,
+                }
+            }
         }
     }
 }
 
+/// Operator along with its operands.
+///
+/// `>` and `=` have exactly two operands, the operators for which [`Oper::is_n_ary`]
+/// have at least two.
 #[derive(Debug, Clone, PartialEq)]
 pub struct Op {
     pub char: Oper,
-    pub children: Box<[Node; 2]>,
+    pub children: Vec<Node>,
 }
 
 impl Op {
     #[inline]
-    pub fn new(char: Oper, children: Box<[Node; 2]>) -> Self {
+    pub fn new(char: Oper, children: Vec<Node>) -> Self {
+        debug_assert!(
+            children.len() == 2 || (char.is_n_ary() && children.len() > 2),
+            "'{}' can't have {} operands",
+            char.ascii_char(),
+            children.len()
+        );
         Self { char, children }
     }
 }
 
-#[derive(Debug, Clone, PartialEq)]
+/// Formula tree.
+///
+/// Every operation on it (parsing, printing, cloning, comparing, dropping, ...)
+/// uses an explicit stack instead of recursion, so huge formulas don't overflow the call stack.
+#[derive(Debug)]
 pub enum Node {
     Value(bool),
-    Variable(char),
+    Variable(Var),
     Neg(Box<Node>),
     Operator(Op),
 }
 
 impl std::fmt::Display for Node {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
-        match self {
-            Node::Operator(Op { children, .. }) => {
-                children[0].fmt(f)?;
-                children[1].fmt(f)?;
-            }
-            Node::Neg(child) => {
-                child.fmt(f)?;
+        for node in self.post_order() {
+            match node {
+                Node::Value(val) => write!(f, "{}", *val as u8)?,
+                Node::Variable(var) => match var.as_letter() {
+                    Some(letter) => write!(f, "{letter}")?,
+                    None => write!(f, "[{var}]")?,
+                },
+                Node::Neg(_) => write!(f, "!")?,
+                Node::Operator(Op { char, children }) => {
+                    // `ABC&&` is a valid RPN for a single `&` of 3 operands
+                    for _ in 1..children.len() {
+                        write!(f, "{}", char.ascii_char())?;
+                    }
+                }
             }
-            Node::Value(_) | Node::Variable(_) => (),
         }
-
-        write!(f, "{}", self.char())?;
         Ok(())
     }
 }
@@ -101,6 +219,57 @@ impl default::Default for Node {
     }
 }
 
+impl Clone for Node {
+    fn clone(&self) -> Self {
+        self.fold(|node, mut children| match node {
+            Node::Value(val) => Node::Value(*val),
+            Node::Variable(var) => Node::Variable(*var),
+            Node::Neg(_) => Node::Neg(Box::new(children.next().unwrap())),
+            Node::Operator(Op { char, .. }) => Node::Operator(Op::new(*char, children.collect())),
+        })
+    }
+}
+
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other).is_eq()
+    }
+}
+
+impl Eq for Node {}
+
+/// Arbitrary but total order, used to sort operands in [`Node::canonicalize_mut`].
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // As long as the nodes match, both trees are walked in lockstep.
+        // Nodes compare their arity, so both walks end at the same time.
+        self.into_iter()
+            .zip(other)
+            .map(|(a, b)| a.shallow_cmp(b))
+            .find(|ord| ord.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Drop for Node {
+    fn drop(&mut self) {
+        // The default drop glue recurses, detach the children onto a heap allocated stack instead
+        if self.children().iter().all(|c| c.children().is_empty()) {
+            return;
+        }
+        let mut stack: Vec<Node> = self.children_mut().iter_mut().map(mem::take).collect();
+        while let Some(mut node) = stack.pop() {
+            stack.extend(node.children_mut().iter_mut().map(mem::take));
+        }
+    }
+}
+
 pub struct NodeIterator<'a> {
     stack: Vec<&'a Node>,
 }
@@ -126,20 +295,129 @@ impl<'a> IntoIterator for &'a Node {
     }
 }
 
+/// Iterator over nodes, children (left to right) before their parent.
+///
+/// This is the order in which they are written in RPN.
+pub struct PostOrderIterator<'a> {
+    /// Nodes along with whether their children were already pushed
+    stack: Vec<(&'a Node, bool)>,
+}
+
+impl<'a> Iterator for PostOrderIterator<'a> {
+    type Item = &'a Node;
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let (node, expanded) = self.stack.pop()?;
+            if expanded || node.children().is_empty() {
+                return Some(node);
+            }
+            self.stack.push((node, true));
+            self.stack
+                .extend(node.children().iter().rev().map(|c| (c, false)));
+        }
+    }
+}
+
 impl Node {
-    pub fn char(&self) -> char {
+    /// Compares the nodes without looking at their children, only at how many there are.
+    fn shallow_cmp(&self, other: &Self) -> Ordering {
+        let rank = |node: &Node| match node {
+            Node::Value(_) => 0,
+            Node::Variable(_) => 1,
+            Node::Neg(_) => 2,
+            Node::Operator(_) => 3,
+        };
+        match (self, other) {
+            (Node::Value(a), Node::Value(b)) => a.cmp(b),
+            (Node::Variable(a), Node::Variable(b)) => a.cmp(b),
+            (Node::Operator(a), Node::Operator(b)) => a
+                .char
+                .cmp(&b.char)
+                .then(a.children.len().cmp(&b.children.len())),
+            _ => rank(self).cmp(&rank(other)),
+        }
+    }
+
+    /// `op` applied to `operands`, or the operand itself if there is only one.
+    pub fn n_ary(op: Oper, mut operands: Vec<Node>) -> Self {
+        match operands.len() {
+            0 => panic!("'{}' needs operands", op.ascii_char()),
+            1 => operands.pop().unwrap(),
+            _ => Node::Operator(Op::new(op, operands)),
+        }
+    }
+
+    /// The operands of `self` if it's an `op`, `self` otherwise.
+    pub fn into_operands(mut self, op: Oper) -> Vec<Node> {
+        match &mut self {
+            Node::Operator(Op { char, children }) if *char == op => mem::take(children),
+            _ => vec![self],
+        }
+    }
+
+    pub fn children(&self) -> &[Node] {
+        match self {
+            Self::Neg(child) => slice::from_ref(&**child),
+            Self::Operator(Op { children, .. }) => children,
+            Self::Value(_) | Self::Variable(_) => &[],
+        }
+    }
+
+    pub fn children_mut(&mut self) -> &mut [Node] {
         match self {
-            Self::Operator(Op { char: c, .. }) => c.ascii_char(),
-            Self::Variable(c) => *c,
-            Self::Value(b) => {
-                if *b {
-                    '1'
-                } else {
-                    '0'
+            Self::Neg(child) => slice::from_mut(&mut **child),
+            Self::Operator(Op { children, .. }) => children,
+            Self::Value(_) | Self::Variable(_) => &mut [],
+        }
+    }
+
+    pub fn post_order(&self) -> PostOrderIterator<'_> {
+        PostOrderIterator {
+            stack: vec![(self, false)],
+        }
+    }
+
+    /// Computes a value for every node from the values of its children, bottom-up.
+    pub fn fold<T, F>(&self, mut f: F) -> T
+    where
+        F: FnMut(&Node, vec::Drain<'_, T>) -> T,
+    {
+        let mut results: Vec<T> = Vec::new();
+        for node in self.post_order() {
+            let first = results.len() - node.children().len();
+            let value = f(node, results.drain(first..));
+            results.push(value);
+        }
+        results.pop().unwrap()
+    }
+
+    /// Calls `f` on every node, after it was called on the node's children.
+    pub fn transform_bottom_up<F: FnMut(&mut Node)>(&mut self, mut f: F) {
+        let mut stack: Vec<(Node, bool)> = vec![(mem::take(self), false)];
+        let mut done: Vec<Node> = Vec::new();
+
+        while let Some((mut node, expanded)) = stack.pop() {
+            let nb_children = node.children().len();
+            if expanded || nb_children == 0 {
+                let first = done.len() - nb_children;
+                for (child, new) in node.children_mut().iter_mut().zip(done.drain(first..)) {
+                    *child = new;
                 }
+                f(&mut node);
+                done.push(node);
+            } else {
+                let parent = stack.len();
+                stack.push((Node::default(), true));
+                stack.extend(
+                    node.children_mut()
+                        .iter_mut()
+                        .rev()
+                        .map(|c| (mem::take(c), false)),
+                );
+                stack[parent].0 = node;
             }
-            Self::Neg(_) => '!',
         }
+        *self = done.pop().unwrap();
     }
 
     pub fn neg(&mut self) {
@@ -149,96 +427,192 @@ impl Node {
         };
     }
 
-    pub fn partial_evaluate(&mut self, var: char, value: bool) {
-        match self {
-            Node::Variable(c) => {
-                if var == *c {
-                    *self = Node::Value(value);
-                }
-            }
-            Node::Operator(Op { char: op, children }) => {
-                children[0].partial_evaluate(var, value);
-                children[1].partial_evaluate(var, value);
-
-                match op {
-                    Oper::Conjunction
-                        if children.iter().any(|c| matches!(c, Node::Value(false))) =>
-                    {
-                        *self = Node::Value(false);
-                    }
-                    Oper::Disjunction
-                        if children.iter().any(|c| matches!(c, Node::Value(true))) =>
-                    {
-                        *self = Node::Value(true);
-                    }
-                    _ => {
-                        if let (Node::Value(left), Node::Value(right)) =
-                            (&children[0], &children[1])
-                        {
-                            let result = match op {
-                                Oper::Conjunction => *left && *right,
-                                Oper::Disjunction => *left || *right,
-                                Oper::ExclusiveDisjunction => *left != *right,
-                                Oper::MaterialCondition => !*left || *right,
-                                Oper::Equivalence => *left == *right,
-                            };
-                            *self = Node::Value(result);
-                        }
+    /// Merges nested chains of the same n-ary operator, e.g. `ABC&&D&` becomes a single `&` of 4 operands.
+    pub fn flatten_mut(&mut self) {
+        // Top-down, so every operand of a long chain is only moved once
+        let mut stack: Vec<&mut Node> = vec![self];
+        while let Some(node) = stack.pop() {
+            node.flatten_node();
+            stack.extend(node.children_mut());
+        }
+    }
+
+    /// Merges the operands of `self` that are the same operator as `self`, at any depth.
+    fn flatten_node(&mut self) {
+        if let Node::Operator(Op { char, children }) = self
+            && char.is_n_ary()
+            && children
+                .iter()
+                .any(|c| matches!(c, Node::Operator(op) if op.char == *char))
+        {
+            let mut flat = Vec::with_capacity(children.len());
+            // In reverse order
+            let mut pending: Vec<Node> = children.drain(..).rev().collect();
+            while let Some(child) = pending.pop() {
+                match child {
+                    Node::Operator(Op { char: op, .. }) if op == *char => {
+                        pending.extend(child.into_operands(op).into_iter().rev());
                     }
+                    _ => flat.push(child),
                 }
             }
-            Self::Neg(child) => {
-                child.partial_evaluate(var, value);
-                match &mut **child {
-                    Self::Value(val) => *self = Self::Value(!*val),
-                    Self::Neg(grand_child) => {
-                        *self = mem::take(&mut *grand_child);
+            *children = flat;
+        }
+    }
+
+    /// Flattens the n-ary operators, sorts their operands and removes the duplicates
+    /// (`AA&` is `A`, `AA^` cancels out), so formulas that only differ by associativity,
+    /// commutativity or idempotence become equal.
+    pub fn canonicalize_mut(&mut self) {
+        self.flatten_mut();
+        self.transform_bottom_up(|node| {
+            // Removing duplicates can leave a single operand of the same operator
+            node.flatten_node();
+            let Node::Operator(Op { char, children }) = node else {
+                return;
+            };
+            if !char.is_n_ary() {
+                return;
+            }
+            children.sort();
+            if *char == Oper::ExclusiveDisjunction {
+                // Equal operands are next to each other, they cancel out by pairs
+                let mut kept: Vec<Node> = Vec::with_capacity(children.len());
+                for child in children.drain(..) {
+                    if kept.last() == Some(&child) {
+                        kept.pop();
+                    } else {
+                        kept.push(child);
                     }
-                    Self::Operator(_) | Self::Variable(_) => (),
                 }
+                *children = kept;
+            } else {
+                children.dedup();
             }
-            Self::Value(_) => (),
-        }
+            match children.len() {
+                0 => *node = Node::Value(false),
+                1 => *node = children.pop().unwrap(),
+                _ => (),
+            }
+        });
     }
 
-    fn inner_parse(s: &mut String) -> Result<Self, MyError> {
-        let val = s.pop().ok_or(MyError::Eof)?;
+    /// Sorted list of the variables used in the formula.
+    pub fn variables(&self) -> Vec<Var> {
+        let mut vars = self
+            .into_iter()
+            .filter_map(|node| match node {
+                Node::Variable(var) => Some(*var),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        vars.sort();
+        vars.dedup();
+        vars
+    }
 
-        match val {
-            '&' | '^' | '|' | '>' | '=' => {
-                let right = Self::inner_parse(s)?;
-                let left = Self::inner_parse(s)?;
-                let node = Self::Operator(Op {
-                    char: Oper::from_ascii(val)?,
-                    children: Box::new([left, right]),
-                });
+    pub fn partial_evaluate(&mut self, var: Var, value: bool) {
+        self.transform_bottom_up(|node| node.partial_evaluate_node(Some((var, value))));
+    }
 
-                Ok(node)
-            }
-            'A'..='Z' => Ok(Self::Variable(val)),
-            '0' | '1' => Ok(Self::Value(val == '1')),
-            '!' => {
-                let mut child = Self::inner_parse(s)?;
-                child.neg();
-                Ok(child)
+    /// Evaluates the parts of the formula that don't depend on any variable.
+    pub fn fold_constants(&mut self) {
+        self.transform_bottom_up(|node| node.partial_evaluate_node(None));
+    }
+
+    /// Does the work of `partial_evaluate` for `self` only, its children are already evaluated.
+    fn partial_evaluate_node(&mut self, assignment: Option<(Var, bool)>) {
+        match self {
+            Node::Variable(c) => {
+                if let Some((var, value)) = assignment
+                    && var == *c
+                {
+                    *self = Node::Value(value);
+                }
             }
-            _ => Err(MyError::InvalidChar(val)),
+            Node::Operator(Op { char: op, children }) => match op {
+                Oper::Conjunction if children.iter().any(|c| matches!(c, Node::Value(false))) => {
+                    *self = Node::Value(false);
+                }
+                Oper::Disjunction if children.iter().any(|c| matches!(c, Node::Value(true))) => {
+                    *self = Node::Value(true);
+                }
+                _ => {
+                    if children.iter().all(|c| matches!(c, Node::Value(_))) {
+                        let values = children.iter().map(|c| matches!(c, Node::Value(true)));
+                        *self = Node::Value(op.apply(values));
+                    }
+                }
+            },
+            Self::Neg(child) => match &mut **child {
+                Self::Value(val) => *self = Self::Value(!*val),
+                Self::Neg(grand_child) => {
+                    *self = mem::take(&mut *grand_child);
+                }
+                Self::Operator(_) | Self::Variable(_) => (),
+            },
+            Self::Value(_) => (),
         }
     }
 
+    /// Parses a formula in RPN.
+    ///
+    /// Variables are single uppercase letters or bracketed identifiers, e.g. `[x12]`.
     pub fn parse<S: Into<String>>(s: S) -> Result<Self, MyError> {
-        let mut s: String = s.into();
-        let res = Self::inner_parse(&mut s)?;
-        debug_assert!(s.is_empty());
-        Ok(res)
+        let s: String = s.into();
+        // Operands along with the offset at which their sub-formula starts
+        let mut stack: Vec<(Node, usize)> = Vec::new();
+        let mut chars = s.char_indices();
+
+        while let Some((i, val)) = chars.next() {
+            let span = Span::of_char(i, val);
+            match val {
+                '&' | '^' | '|' | '>' | '=' => {
+                    let (Some((right, _)), Some((left, start))) = (stack.pop(), stack.pop()) else {
+                        return Err(MyError::MissingOperand(val, span));
+                    };
+                    let node =
+                        Self::Operator(Op::new(Oper::from_ascii(val).unwrap(), vec![left, right]));
+                    stack.push((node, start));
+                }
+                'A'..='Z' => stack.push((Self::Variable(Var::from(val)), i)),
+                '[' => {
+                    let Some((end, _)) = chars.find(|&(_, c)| c == ']') else {
+                        return Err(MyError::Unclosed('[', span));
+                    };
+                    let name = &s[i + 1..end];
+                    if !Var::is_valid_name(name) {
+                        return Err(MyError::InvalidVariable(
+                            name.to_string(),
+                            Span::new(i, end + 1),
+                        ));
+                    }
+                    stack.push((Self::Variable(Var::new(name)), i));
+                }
+                '0' | '1' => stack.push((Self::Value(val == '1'), i)),
+                '!' => {
+                    let (child, _) = stack.last_mut().ok_or(MyError::MissingOperand(val, span))?;
+                    child.neg();
+                }
+                _ => return Err(MyError::InvalidChar(val, span)),
+            }
+        }
+        match stack.len() {
+            0 => Err(MyError::Eof(Span::at(0))),
+            1 => Ok(stack.pop().unwrap().0),
+            _ => Err(MyError::TrailingOperands(Span::new(
+                0,
+                stack.last().unwrap().1,
+            ))),
+        }
     }
 
     #[cfg(test)]
-    pub fn new_random(variables: &[char]) -> Self {
+    pub fn new_random<V: Into<Var> + Copy>(variables: &[V]) -> Self {
         let nodekind = rand::random::<usize>() % 4;
 
         match nodekind {
-            0 => Node::Variable(variables[rand::random::<usize>() % variables.len()]),
+            0 => Node::Variable(variables[rand::random::<usize>() % variables.len()].into()),
             1 => Node::Value(rand::random::<bool>()),
             2 => Node::Operator(Op {
                 char: [
@@ -248,30 +622,54 @@ impl Node {
                     Oper::MaterialCondition,
                     Oper::Equivalence,
                 ][rand::random::<usize>() % 5],
-                children: Box::new([Self::new_random(variables), Self::new_random(variables)]),
+                children: vec![Self::new_random(variables), Self::new_random(variables)],
             }),
-            3 => Node::Neg(Box::new(Self::new_random(variables))),
+            3 => {
+                // As parsed, `!!` collapses
+                let mut node = Self::new_random(variables);
+                node.neg();
+                node
+            }
             _ => unreachable!(),
         }
     }
 
+    /// Calls `f` on every operator and negation, and on the operand of negations, top-down.
     pub fn recursive_edit_operators<F: FnMut(&mut Self)>(&mut self, f: &mut F) {
-        if let Node::Neg(_) = self {
-            f(self);
-        }
-        if let Node::Neg(child) = self {
-            f(child);
-        }
-        if let Node::Operator(_) = self {
-            f(self);
-        }
-        if let Node::Operator(Op { children, .. }) = self {
-            children[0].recursive_edit_operators(f);
-            children[1].recursive_edit_operators(f);
+        let mut stack: Vec<&mut Node> = vec![self];
+        while let Some(node) = stack.pop() {
+            if let Node::Neg(_) = node {
+                f(node);
+            }
+            if let Node::Neg(child) = node {
+                f(child);
+            }
+            if let Node::Operator(_) = node {
+                f(node);
+            }
+            if let Node::Operator(Op { children, .. }) = node {
+                stack.extend(children.iter_mut().rev());
+            }
         }
     }
 }
 
+/// 10M tokens, nested 6.7M levels deep
+#[cfg(test)]
+pub fn huge_formula() -> String {
+    let mut formula = String::from("A");
+    formula.push_str(&"!B&".repeat(3_333_334));
+    formula
+}
+
+/// 10M tokens, a chain of 5M `op`s
+#[cfg(test)]
+pub fn huge_chain(op: char) -> String {
+    let mut formula = String::from("A");
+    formula.push_str(&format!("B{op}").repeat(5_000_000));
+    formula
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -282,7 +680,7 @@ mod tests {
             Node::parse("AB|!").unwrap(),
             Node::Neg(Box::new(Node::Operator(Op::new(
                 Oper::Disjunction,
-                Box::new([Node::Variable('A'), Node::Variable('B')])
+                vec![Node::Variable('A'.into()), Node::Variable('B'.into())]
             ))))
         );
     }
@@ -302,15 +700,55 @@ mod tests {
         assert_eq!(regurgitate("ABCD&&&"), "ABCD&&&");
     }
 
+    #[test]
+    fn named_variables() {
+        let tree = Node::parse("[x12][carry_3]&A|").unwrap();
+        assert_eq!(tree.to_string(), "[x12][carry_3]&A|");
+        assert_eq!(
+            tree.variables(),
+            vec![Var::new("A"), Var::new("carry_3"), Var::new("x12")]
+        );
+        assert_eq!(Node::parse("[A]").unwrap(), Node::parse("A").unwrap());
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert!(matches!(Node::parse(""), Err(MyError::Eof(_))));
+        assert!(matches!(
+            Node::parse("AB&C"),
+            Err(MyError::TrailingOperands(Span { start: 0, end: 3 }))
+        ));
+        assert!(matches!(
+            Node::parse("AB|&"),
+            Err(MyError::MissingOperand('&', Span { start: 3, end: 4 }))
+        ));
+        assert!(matches!(
+            Node::parse("!"),
+            Err(MyError::MissingOperand('!', Span { start: 0, end: 1 }))
+        ));
+        assert!(matches!(
+            Node::parse("Aé&"),
+            Err(MyError::InvalidChar('é', Span { start: 1, end: 3 }))
+        ));
+        assert!(matches!(
+            Node::parse("A[x1&"),
+            Err(MyError::Unclosed('[', Span { start: 1, end: 2 }))
+        ));
+        assert!(matches!(
+            Node::parse("A[1x]&"),
+            Err(MyError::InvalidVariable(name, Span { start: 1, end: 5 })) if name == "1x"
+        ));
+    }
+
     #[test]
     fn partial_evaluation() {
         let raw_formula = "AB>C&!D|A^B=";
         // let final_formula = Node::parse("01>1&!0|0^1=").unwrap();
         let mut partialy_evaluated = Node::parse(raw_formula).unwrap();
-        partialy_evaluated.partial_evaluate('A', false);
-        partialy_evaluated.partial_evaluate('B', true);
-        partialy_evaluated.partial_evaluate('C', true);
-        partialy_evaluated.partial_evaluate('D', false);
+        partialy_evaluated.partial_evaluate('A'.into(), false);
+        partialy_evaluated.partial_evaluate('B'.into(), true);
+        partialy_evaluated.partial_evaluate('C'.into(), true);
+        partialy_evaluated.partial_evaluate('D'.into(), false);
         assert_eq!(partialy_evaluated, Node::Value(false));
     }
 
@@ -326,18 +764,77 @@ mod tests {
         assert_eq!(doit("A!"), "A");
     }
 
-    #[ignore = "too slow"]
+    #[test]
+    fn n_ary() {
+        let mut tree = Node::parse("AB&C&D!&E|").unwrap();
+        tree.flatten_mut();
+        let Node::Operator(Op { children, .. }) = &tree else {
+            panic!("{tree:?}");
+        };
+        assert_eq!(children.len(), 2);
+        assert_eq!(children[0].children().len(), 4);
+        assert_eq!(tree.to_string(), "ABCD!&&&E|");
+        assert_eq!(
+            Node::parse(tree.to_string()).unwrap().to_string(),
+            "ABCD!&&&E|"
+        );
+
+        let canonical = |formula: &str| {
+            let mut tree = Node::parse(formula).unwrap();
+            tree.canonicalize_mut();
+            tree.to_string()
+        };
+        assert_eq!(canonical("CA&B&"), "ABC&&");
+        assert_eq!(canonical("BA|AB|&"), "AB|");
+        assert_eq!(canonical("AB&A&"), "AB&");
+        assert_eq!(canonical("AB^A^"), "B");
+        assert_eq!(canonical("AA^"), "0");
+        assert_eq!(canonical("BA>"), "BA>");
+        assert_eq!(canonical("[x10][x2]|B!|1|"), "1[x2][x10]B!|||");
+    }
+
+    #[test]
+    fn n_ary_partial_evaluation() {
+        let mut tree = Node::parse("AB^C^").unwrap();
+        tree.flatten_mut();
+        tree.partial_evaluate('A'.into(), true);
+        tree.partial_evaluate('B'.into(), true);
+        assert_eq!(tree.to_string(), "11C^^");
+        tree.partial_evaluate('C'.into(), true);
+        assert_eq!(tree, Node::Value(true));
+    }
+
     #[test]
     fn can_parse_huge_formula() {
-        loop {
-            let mut tree = Node::new_random(&['A', 'B', 'C', 'D', 'E', 'F', 'G', 'H', 'I', 'J']);
-            tree.to_primitive_connectives_mut();
-            let formula = tree.to_string();
-            if formula.len() < 100_000 {
-                continue;
+        let formula = huge_formula();
+        let mut tree = Node::parse(formula.as_str()).unwrap();
+        assert_eq!(tree.to_string(), formula);
+        assert_eq!(tree.clone(), tree);
+
+        tree.partial_evaluate('B'.into(), false);
+        assert_eq!(tree, Node::Value(false));
+
+        let chain = huge_chain('&');
+        let mut tree = Node::parse(chain.as_str()).unwrap();
+        assert_eq!(tree.to_string(), chain);
+        tree.partial_evaluate('A'.into(), false);
+        assert_eq!(tree, Node::Value(false));
+
+        let tree = Node::parse(huge_formula()).unwrap();
+        drop(tree);
+    }
+
+    #[test]
+    fn edit_huge_formula() {
+        let mut tree = Node::parse(huge_chain('&')).unwrap();
+        let mut calls = 0;
+        tree.recursive_edit_operators(&mut |node| {
+            calls += 1;
+            if let Node::Operator(op) = node {
+                op.char = Oper::Disjunction;
             }
-            Node::parse(formula).unwrap();
-            break;
-        }
+        });
+        assert_eq!(calls, 5_000_000);
+        assert_eq!(tree.to_string(), huge_chain('|'));
     }
 }
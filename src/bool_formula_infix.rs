@@ -0,0 +1,531 @@
+//! Infix notation, e.g. `(A & B) | !C => D`.
+//!
+//! The parser produces the same [`Node`] tree as the RPN [`Node::parse`],
+//! the printer writes it back with as few parentheses as possible.
+//! Variables are identifiers (`A`, `x12`, `carry_3`) or bracketed like in RPN (`[x12]`).
+//! Precedence, from tightest to loosest: `!` `&` `^` `|` `>` `=`.
+//! `>` is right associative, the other binary operators are left associative.
+use std::fmt;
+use std::iter::Peekable;
+use std::str::CharIndices;
+
+use crate::bool_formula_ast::{MyError, Node, Op, Oper};
+use crate::diagnostic::Span;
+use crate::variable::Var;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Operand(Node),
+    Not,
+    Binary(Oper),
+    OpenParen,
+    CloseParen,
+}
+
+struct Lexer<'a> {
+    formula: &'a str,
+    chars: Peekable<CharIndices<'a>>,
+}
+
+impl Lexer<'_> {
+    /// Consumes the rest of a multi-character glyph (`=>`, `<=>`).
+    ///
+    /// Returns the end offset of the glyph.
+    fn expect(&mut self, expected: char) -> Result<usize, MyError> {
+        match self.chars.next() {
+            Some((i, c)) if c == expected => Ok(i + c.len_utf8()),
+            Some((i, c)) => Err(MyError::InvalidChar(c, Span::of_char(i, c))),
+            None => Err(MyError::Eof(Span::at(self.formula.len()))),
+        }
+    }
+
+    /// Consumes the rest of an identifier starting at `start`.
+    fn identifier(&mut self, start: usize) -> Span {
+        let mut end = start + 1;
+        while let Some((i, c)) = self
+            .chars
+            .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+        {
+            end = i + c.len_utf8();
+        }
+        Span::new(start, end)
+    }
+
+    /// Consumes the rest of a bracketed variable name starting at `start`.
+    fn bracketed(&mut self, start: usize) -> Result<(Var, Span), MyError> {
+        let Some((end, _)) = self.chars.find(|&(_, c)| c == ']') else {
+            return Err(MyError::Unclosed('[', Span::of_char(start, '[')));
+        };
+        let name = &self.formula[start + 1..end];
+        let span = Span::new(start, end + 1);
+        if !Var::is_valid_name(name) {
+            return Err(MyError::InvalidVariable(name.to_string(), span));
+        }
+        Ok((Var::new(name), span))
+    }
+}
+
+impl Iterator for Lexer<'_> {
+    type Item = Result<(Token, Span), MyError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, c) = self.chars.find(|(_, c)| !c.is_whitespace())?;
+        let mut span = Span::of_char(i, c);
+
+        let token = match c {
+            c if c.is_ascii_alphabetic() || c == '_' => {
+                span = self.identifier(i);
+                let name = &self.formula[span.start..span.end];
+                Token::Operand(Node::Variable(Var::new(name)))
+            }
+            '[' => match self.bracketed(i) {
+                Ok((var, var_span)) => {
+                    span = var_span;
+                    Token::Operand(Node::Variable(var))
+                }
+                Err(e) => return Some(Err(e)),
+            },
+            '0' | '1' => Token::Operand(Node::Value(c == '1')),
+            '!' | '¬' => Token::Not,
+            '(' => Token::OpenParen,
+            ')' => Token::CloseParen,
+            '=' if matches!(self.chars.peek(), Some((_, '>'))) => {
+                self.chars.next();
+                span.end += 1;
+                Token::Binary(Oper::MaterialCondition)
+            }
+            '<' => match self.expect('=').and_then(|_| self.expect('>')) {
+                Ok(end) => {
+                    span.end = end;
+                    Token::Binary(Oper::Equivalence)
+                }
+                // Not the start of `<=>`, `<` is the bad character and not what follows it
+                Err(MyError::InvalidChar(..)) => return Some(Err(MyError::InvalidChar(c, span))),
+                Err(e) => return Some(Err(e)),
+            },
+            _ => match Oper::from_ascii(c).or_else(|| Oper::from_utf8(c)) {
+                Some(op) => Token::Binary(op),
+                None => return Some(Err(MyError::InvalidChar(c, span))),
+            },
+        };
+        Some(Ok((token, span)))
+    }
+}
+
+/// Pending entries of the shunting-yard operator stack.
+enum Pending {
+    Not,
+    Binary(Oper),
+    OpenParen(Span),
+}
+
+/// Pops an operator from `pending` and applies it to the operands on top of `output`.
+fn reduce(pending: Pending, output: &mut Vec<Node>) {
+    match pending {
+        Pending::Not => {
+            // The parser only pushes `!` when an operand must follow
+            output.last_mut().expect("missing operand for '!'").neg();
+        }
+        Pending::Binary(op) => {
+            let right = output.pop().expect("missing right operand");
+            let left = output.pop().expect("missing left operand");
+            output.push(Node::Operator(Op::new(op, vec![left, right])));
+        }
+        Pending::OpenParen(_) => unreachable!("parentheses are never reduced"),
+    }
+}
+
+impl Node {
+    /// Parses a formula written in infix notation.
+    ///
+    /// Accepts the ASCII (`& | ^ > =`) and UTF-8 (`∧ ∨ ⊕ ⇒ ⇔`) operator glyphs,
+    /// as well as `=>` and `<=>`, `!` or `¬` for negation and parentheses.
+    pub fn parse_infix(s: &str) -> Result<Self, MyError> {
+        let lexer = Lexer {
+            formula: s,
+            chars: s.char_indices().peekable(),
+        };
+        let mut output: Vec<Node> = Vec::new();
+        let mut pending: Vec<Pending> = Vec::new();
+        // Whether the next token has to start an operand (value, variable, `!` or `(`)
+        let mut expect_operand = true;
+
+        for token in lexer {
+            let (token, span) = token?;
+            match token {
+                Token::Operand(node) if expect_operand => {
+                    output.push(node);
+                    expect_operand = false;
+                }
+                Token::Not if expect_operand => pending.push(Pending::Not),
+                Token::OpenParen if expect_operand => pending.push(Pending::OpenParen(span)),
+                Token::CloseParen if !expect_operand => loop {
+                    match pending.pop() {
+                        Some(Pending::OpenParen(_)) => break,
+                        Some(p) => reduce(p, &mut output),
+                        None => return Err(MyError::Unexpected(")".to_string(), span)),
+                    }
+                },
+                Token::Binary(op) if !expect_operand => {
+                    while let Some(top) = pending.last() {
+                        let reduce_top = match top {
+                            Pending::Not => true,
+                            Pending::Binary(top_op) => {
+                                top_op.precedence() > op.precedence()
+                                    || (top_op.precedence() == op.precedence()
+                                        && !op.is_right_associative())
+                            }
+                            Pending::OpenParen(_) => false,
+                        };
+                        if !reduce_top {
+                            break;
+                        }
+                        reduce(pending.pop().unwrap(), &mut output);
+                    }
+                    pending.push(Pending::Binary(op));
+                    expect_operand = true;
+                }
+                _ => {
+                    let text = s[span.start..span.end].to_string();
+                    return Err(MyError::Unexpected(text, span));
+                }
+            }
+        }
+        if expect_operand {
+            return Err(MyError::Eof(Span::at(s.len())));
+        }
+        while let Some(p) = pending.pop() {
+            if let Pending::OpenParen(span) = p {
+                return Err(MyError::Unclosed('(', span));
+            }
+            reduce(p, &mut output);
+        }
+        debug_assert_eq!(output.len(), 1);
+        Ok(output.pop().unwrap())
+    }
+}
+
+/// Glyphs used by [`Node::infix`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Notation {
+    /// `!A & B | C ^ D > E = F`, can be read back by [`Node::parse_infix`]
+    #[default]
+    Ascii,
+    /// `¬A ∧ B ∨ C ⊕ D ⇒ E ⇔ F`, can be read back by [`Node::parse_infix`]
+    Unicode,
+    /// `\lnot A \land B \lor C \oplus D \Rightarrow E \Leftrightarrow F`, for LaTeX math mode
+    Latex,
+}
+
+impl Notation {
+    fn binary(&self, op: Oper) -> &'static str {
+        match (self, op) {
+            (Self::Ascii, Oper::Conjunction) => " & ",
+            (Self::Ascii, Oper::Disjunction) => " | ",
+            (Self::Ascii, Oper::ExclusiveDisjunction) => " ^ ",
+            (Self::Ascii, Oper::MaterialCondition) => " > ",
+            (Self::Ascii, Oper::Equivalence) => " = ",
+            (Self::Unicode, Oper::Conjunction) => " ∧ ",
+            (Self::Unicode, Oper::Disjunction) => " ∨ ",
+            (Self::Unicode, Oper::ExclusiveDisjunction) => " ⊕ ",
+            (Self::Unicode, Oper::MaterialCondition) => " ⇒ ",
+            (Self::Unicode, Oper::Equivalence) => " ⇔ ",
+            (Self::Latex, Oper::Conjunction) => " \\land ",
+            (Self::Latex, Oper::Disjunction) => " \\lor ",
+            (Self::Latex, Oper::ExclusiveDisjunction) => " \\oplus ",
+            (Self::Latex, Oper::MaterialCondition) => " \\Rightarrow ",
+            (Self::Latex, Oper::Equivalence) => " \\Leftrightarrow ",
+        }
+    }
+
+    fn negation(&self) -> &'static str {
+        match self {
+            Self::Ascii => "!",
+            Self::Unicode => "¬",
+            Self::Latex => "\\lnot ",
+        }
+    }
+
+    fn value(&self, value: bool) -> &'static str {
+        match (self, value) {
+            (Self::Latex, false) => "\\bot",
+            (Self::Latex, true) => "\\top",
+            (_, false) => "0",
+            (_, true) => "1",
+        }
+    }
+
+    fn variable(&self, f: &mut fmt::Formatter<'_>, var: Var) -> fmt::Result {
+        if *self != Self::Latex {
+            return write!(f, "{var}");
+        }
+        // `x12` -> `x_{12}`, `carry_3` -> `\mathit{carry}_{3}`
+        let name = var.name();
+        let base = name.trim_end_matches(|c: char| c.is_ascii_digit());
+        let index = &name[base.len()..];
+        let base = match base.strip_suffix('_') {
+            Some(stripped) if !index.is_empty() && !stripped.is_empty() => stripped,
+            _ => base,
+        };
+        let base = base.replace('_', "\\_");
+        match base.chars().count() {
+            1 => write!(f, "{base}")?,
+            _ => write!(f, "\\mathit{{{base}}}")?,
+        }
+        if !index.is_empty() {
+            write!(f, "_{{{index}}}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Whether `child` must be parenthesized as an operand of `parent`.
+///
+/// `parent` is `None` for negations, `right` tells whether the child is not the first operand.
+fn needs_parens(parent: Option<Oper>, child: &Node, right: bool) -> bool {
+    let Node::Operator(Op { char: child_op, .. }) = child else {
+        return false;
+    };
+    let Some(parent_op) = parent else {
+        return true;
+    };
+    match child_op.precedence().cmp(&parent_op.precedence()) {
+        std::cmp::Ordering::Less => true,
+        std::cmp::Ordering::Greater => false,
+        // Same precedence means same operator
+        std::cmp::Ordering::Equal => {
+            !parent_op.is_associative() && right != parent_op.is_right_associative()
+        }
+    }
+}
+
+/// Infix rendering of a [`Node`], see [`Node::infix`].
+pub struct Infix<'a> {
+    node: &'a Node,
+    notation: Notation,
+}
+
+impl fmt::Display for Infix<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        enum Item<'a> {
+            Node(&'a Node),
+            Text(&'static str),
+        }
+
+        // Items are pushed in reverse
+        fn push_operand<'a>(stack: &mut Vec<Item<'a>>, parens: bool, node: &'a Node) {
+            if parens {
+                stack.push(Item::Text(")"));
+                stack.push(Item::Node(node));
+                stack.push(Item::Text("("));
+            } else {
+                stack.push(Item::Node(node));
+            }
+        }
+
+        let mut stack = vec![Item::Node(self.node)];
+        while let Some(item) = stack.pop() {
+            match item {
+                Item::Text(text) => f.write_str(text)?,
+                Item::Node(Node::Value(val)) => f.write_str(self.notation.value(*val))?,
+                Item::Node(Node::Variable(var)) => self.notation.variable(f, *var)?,
+                Item::Node(Node::Neg(child)) => {
+                    push_operand(&mut stack, needs_parens(None, child, false), child);
+                    f.write_str(self.notation.negation())?;
+                }
+                Item::Node(Node::Operator(Op { char, children })) => {
+                    for (i, child) in children.iter().enumerate().rev() {
+                        push_operand(&mut stack, needs_parens(Some(*char), child, i > 0), child);
+                        if i > 0 {
+                            stack.push(Item::Text(self.notation.binary(*char)));
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Node {
+    /// Displays the formula in infix notation with the minimum amount of parentheses.
+    ///
+    /// Associative operators are not parenthesized, so `ABC&&` is printed `A & B & C`
+    /// even though [`Node::parse_infix`] reads it back as `AB&C&`.
+    pub fn infix(&self, notation: Notation) -> Infix<'_> {
+        Infix {
+            node: self,
+            notation,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ex04_truth_table::TruthTable;
+
+    fn assert_same_as_rpn(infix: &str, rpn: &str) {
+        assert_eq!(
+            Node::parse_infix(infix).unwrap(),
+            Node::parse(rpn).unwrap(),
+            "{infix}"
+        );
+    }
+
+    #[test]
+    fn parse_infix() {
+        assert_same_as_rpn("A", "A");
+        assert_same_as_rpn("!1", "1!");
+        assert_same_as_rpn("(A & B) | !C => D", "AB&C!|D>");
+        assert_same_as_rpn("A | B & C", "ABC&|");
+        assert_same_as_rpn("A & B ^ C | D > E = F", "AB&C^D|E>F=");
+        assert_same_as_rpn("A = B > C | D ^ E & F", "ABCDEF&^|>=");
+        assert_same_as_rpn("!(A | B)", "AB|!");
+        assert_same_as_rpn("!!A", "A");
+        assert_same_as_rpn("((((A))))", "A");
+    }
+
+    #[test]
+    fn associativity() {
+        assert_same_as_rpn("A & B & C", "AB&C&");
+        assert_same_as_rpn("A = B = C", "AB=C=");
+        assert_same_as_rpn("A > B > C", "ABC>>");
+        assert_same_as_rpn("A => B => C", "ABC>>");
+    }
+
+    #[test]
+    fn glyphs() {
+        assert_same_as_rpn("¬A ∧ B ∨ C ⊕ D ⇒ E ⇔ F", "A!B&CD^|E>F=");
+        assert_same_as_rpn("A <=> B => C", "ABC>=");
+        assert_same_as_rpn("A=B", "AB=");
+    }
+
+    #[test]
+    fn named_variables() {
+        assert_same_as_rpn("x12 & carry_3 | [y]", "[x12][carry_3]&[y]|");
+        assert_same_as_rpn("!AB=>_", "[AB]![_]>");
+    }
+
+    #[test]
+    fn errors() {
+        let err = |formula| Node::parse_infix(formula).unwrap_err();
+
+        assert!(matches!(err(""), MyError::Eof(Span { start: 0, end: 0 })));
+        assert!(matches!(
+            err("A &"),
+            MyError::Eof(Span { start: 3, end: 3 })
+        ));
+        assert!(matches!(
+            err("A & (B | C"),
+            MyError::Unclosed('(', Span { start: 4, end: 5 })
+        ));
+        assert!(matches!(
+            err("A & B)"),
+            MyError::Unexpected(t, Span { start: 5, end: 6 }) if t == ")"
+        ));
+        assert!(matches!(
+            err("A x12"),
+            MyError::Unexpected(t, Span { start: 2, end: 5 }) if t == "x12"
+        ));
+        assert!(matches!(
+            err("A & => B"),
+            MyError::Unexpected(t, Span { start: 4, end: 6 }) if t == "=>"
+        ));
+        assert!(matches!(
+            err("()"),
+            MyError::Unexpected(t, Span { start: 1, end: 2 }) if t == ")"
+        ));
+        assert!(matches!(
+            err("A < B"),
+            MyError::InvalidChar('<', Span { start: 2, end: 3 })
+        ));
+        assert!(matches!(
+            err("A <=x B"),
+            MyError::InvalidChar('<', Span { start: 2, end: 3 })
+        ));
+        assert!(matches!(
+            err("A <="),
+            MyError::Eof(Span { start: 4, end: 4 })
+        ));
+        assert!(matches!(
+            err("¬$ ∧ B"),
+            MyError::InvalidChar('$', Span { start: 2, end: 3 })
+        ));
+        assert!(matches!(
+            err("A | [x"),
+            MyError::Unclosed('[', Span { start: 4, end: 5 })
+        ));
+        assert!(matches!(
+            err("A | [x y]"),
+            MyError::InvalidVariable(name, Span { start: 4, end: 9 }) if name == "x y"
+        ));
+    }
+
+    fn infix(rpn: &str, notation: Notation) -> String {
+        Node::parse(rpn).unwrap().infix(notation).to_string()
+    }
+
+    #[test]
+    fn print_infix() {
+        assert_eq!(infix("AB&C!|D>", Notation::Ascii), "A & B | !C > D");
+        assert_eq!(infix("ABC&|", Notation::Ascii), "A | B & C");
+        assert_eq!(infix("AB|C&", Notation::Ascii), "(A | B) & C");
+        assert_eq!(infix("AB|!", Notation::Ascii), "!(A | B)");
+        assert_eq!(infix("AB!&!", Notation::Ascii), "!(A & !B)");
+        assert_eq!(infix("ABC&&", Notation::Ascii), "A & B & C");
+        assert_eq!(infix("ABC>>", Notation::Ascii), "A > B > C");
+        assert_eq!(infix("AB>C>", Notation::Ascii), "(A > B) > C");
+        assert_eq!(infix("AB=C^", Notation::Ascii), "(A = B) ^ C");
+        assert_eq!(infix("[x1]1^", Notation::Ascii), "x1 ^ 1");
+
+        let mut n_ary = Node::parse("AB|C|DE|&").unwrap();
+        n_ary.flatten_mut();
+        assert_eq!(
+            n_ary.infix(Notation::Ascii).to_string(),
+            "(A | B | C) & (D | E)"
+        );
+    }
+
+    #[test]
+    fn print_unicode_and_latex() {
+        assert_eq!(
+            infix("A!B&C|D^E>F=", Notation::Unicode),
+            "(¬A ∧ B ∨ C) ⊕ D ⇒ E ⇔ F"
+        );
+        assert_eq!(
+            infix("A!B&CD^|E>F=", Notation::Unicode),
+            "¬A ∧ B ∨ C ⊕ D ⇒ E ⇔ F"
+        );
+        assert_eq!(
+            infix("A![x12]&[carry_3]|0>", Notation::Latex),
+            "\\lnot A \\land x_{12} \\lor \\mathit{carry}_{3} \\Rightarrow \\bot"
+        );
+        assert_eq!(
+            infix("[x_1][a_b]&", Notation::Latex),
+            "x_{1} \\land \\mathit{a\\_b}"
+        );
+    }
+
+    #[test]
+    fn print_parse_round_trip() {
+        for _ in 0..100 {
+            let tree = Node::new_random(&["A", "B", "x1", "x2"].map(Var::new));
+            let rpn = tree.to_string();
+            let truth_table = TruthTable::compute(&rpn).unwrap();
+            for notation in [Notation::Ascii, Notation::Unicode] {
+                let printed = tree.infix(notation).to_string();
+                let parsed = Node::parse_infix(&printed).unwrap();
+                println!("{rpn} -> {printed} -> {parsed}");
+                assert_eq!(
+                    TruthTable::compute_with_given_vars(
+                        &parsed.to_string(),
+                        truth_table.variables().to_vec()
+                    )
+                    .unwrap(),
+                    truth_table
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! Formulas compiled to a flat stack machine program, for evaluating one formula many times.
+//!
+//! ```text
+//! AB&C!|  ->  load 0, load 1, apply & 2, load 2, not, apply | 2
+//! ```
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bool_formula_ast::{MyError, Node, Op, Oper};
+use crate::variable::Var;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instr {
+    /// Pushes a constant
+    Const(bool),
+    /// Pushes the value of the variable in this slot of [`Program::variables`]
+    Load(u32),
+    /// Negates the top of the stack
+    Not,
+    /// Pops that many values and pushes the operator applied to them
+    Apply(Oper, u32),
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Const(val) => write!(f, "const {}", *val as u8),
+            Self::Load(slot) => write!(f, "load {slot}"),
+            Self::Not => write!(f, "not"),
+            Self::Apply(op, n) => write!(f, "apply {} {n}", op.ascii_char()),
+        }
+    }
+}
+
+/// Compiled formula, evaluated by a [`Vm`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    code: Vec<Instr>,
+    variables: Vec<Var>,
+    /// Size the stack reaches while running
+    max_depth: usize,
+}
+
+impl Program {
+    /// Compiles `node`, its variables get the slots of [`Node::variables`].
+    pub fn compile(node: &Node) -> Self {
+        Self::compile_with_vars(node, node.variables()).unwrap()
+    }
+
+    /// Compiles `node` with `variables` as slots, they may include variables `node` doesn't use.
+    pub fn compile_with_vars(node: &Node, variables: Vec<Var>) -> Result<Self, MyError> {
+        assert!(variables.len() <= u32::MAX as usize, "too many variables");
+        let slots = variables
+            .iter()
+            .enumerate()
+            .map(|(slot, &var)| (var, slot as u32))
+            .collect::<HashMap<_, _>>();
+        let mut code = Vec::new();
+        let (mut depth, mut max_depth) = (0, 0);
+
+        for node in node.post_order() {
+            let instr = match node {
+                Node::Value(val) => Instr::Const(*val),
+                Node::Variable(var) => {
+                    Instr::Load(*slots.get(var).ok_or(MyError::UnsetVariable(*var))?)
+                }
+                Node::Neg(_) => Instr::Not,
+                Node::Operator(Op { char, children }) => {
+                    Instr::Apply(*char, children.len().try_into().expect("too many operands"))
+                }
+            };
+            match instr {
+                Instr::Const(_) | Instr::Load(_) => depth += 1,
+                Instr::Not => (),
+                Instr::Apply(_, n) => depth -= n as usize - 1,
+            }
+            max_depth = max_depth.max(depth);
+            code.push(instr);
+        }
+        Ok(Self {
+            code,
+            variables,
+            max_depth,
+        })
+    }
+
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    /// The variables of the slots, `values[i]` is the value of `variables()[i]` in [`Vm::evaluate`].
+    pub fn variables(&self) -> &[Var] {
+        &self.variables
+    }
+
+    /// Convenience for a single evaluation, see [`Vm::evaluate`].
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        Vm::new(self).evaluate(values)
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, instr) in self.code.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{instr}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Runs a [`Program`], keeping its stack allocated between evaluations.
+pub struct Vm<'a> {
+    program: &'a Program,
+    stack: Vec<bool>,
+}
+
+impl<'a> Vm<'a> {
+    pub fn new(program: &'a Program) -> Self {
+        Self {
+            program,
+            stack: Vec::with_capacity(program.max_depth),
+        }
+    }
+
+    /// `values[i]` is the value of the variable in slot `i`.
+    pub fn evaluate(&mut self, values: &[bool]) -> bool {
+        assert_eq!(values.len(), self.program.variables.len());
+        self.run(|slot| values[slot as usize])
+    }
+
+    /// Evaluates the row `row` of the truth table over [`Program::variables`],
+    /// with the first variable as most significant bit like [`Bitmask`](crate::ex03_boolean_evaluation::Bitmask).
+    pub fn evaluate_row(&mut self, row: u64) -> bool {
+        let last = self.program.variables.len() as u32;
+        assert!(last <= 64, "more than 64 variables");
+        self.run(|slot| row >> (last - 1 - slot) & 1 == 1)
+    }
+
+    #[inline]
+    fn run<F: Fn(u32) -> bool>(&mut self, value_of: F) -> bool {
+        let stack = &mut self.stack;
+        stack.clear();
+        for instr in self.program.code.iter() {
+            match *instr {
+                Instr::Const(val) => stack.push(val),
+                Instr::Load(slot) => stack.push(value_of(slot)),
+                Instr::Not => {
+                    let top = stack.last_mut().unwrap();
+                    *top = !*top;
+                }
+                Instr::Apply(op, 2) => {
+                    let right = stack.pop().unwrap();
+                    let left = stack.last_mut().unwrap();
+                    *left = match op {
+                        Oper::Conjunction => *left && right,
+                        Oper::Disjunction => *left || right,
+                        Oper::ExclusiveDisjunction => *left != right,
+                        Oper::MaterialCondition => !*left || right,
+                        Oper::Equivalence => *left == right,
+                    };
+                }
+                Instr::Apply(op, n) => {
+                    let first = stack.len() - n as usize;
+                    let result = op.apply(stack.drain(first..));
+                    stack.push(result);
+                }
+            }
+        }
+        stack.pop().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ex03_boolean_evaluation::eval_formula;
+
+    #[test]
+    fn compile() {
+        let program = Program::compile(&Node::parse("AB&C!|").unwrap());
+        assert_eq!(
+            program.to_string(),
+            "load 0, load 1, apply & 2, load 2, not, apply | 2"
+        );
+        assert_eq!(program.max_depth, 2);
+
+        let mut tree = Node::parse("AB|C|1|").unwrap();
+        tree.flatten_mut();
+        let program = Program::compile(&tree);
+        assert_eq!(
+            program.to_string(),
+            "load 0, load 1, load 2, const 1, apply | 4"
+        );
+        assert_eq!(program.max_depth, 4);
+
+        assert!(matches!(
+            Program::compile_with_vars(&tree, vec![Var::from('A')]),
+            Err(MyError::UnsetVariable(v)) if v == Var::from('B')
+        ));
+    }
+
+    #[test]
+    fn matches_eval_formula() {
+        let vars = ['A', 'B', 'C', 'D', 'E'];
+        for _ in 0..100 {
+            let mut tree = Node::new_random(&vars);
+            let formula = tree.to_string();
+            if rand::random() {
+                tree.flatten_mut();
+            }
+            let program = Program::compile_with_vars(&tree, vars.map(Var::from).to_vec()).unwrap();
+            let mut vm = Vm::new(&program);
+
+            for row in 0..1 << vars.len() {
+                // Same formula with the variables replaced by their value
+                let values = formula
+                    .chars()
+                    .map(|c| match vars.iter().position(|&v| v == c) {
+                        Some(i) => char::from(b'0' + (row >> (vars.len() - 1 - i) & 1) as u8),
+                        None => c,
+                    })
+                    .collect::<String>();
+                assert_eq!(vm.evaluate_row(row), eval_formula(&values), "{values}");
+            }
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release"]
+    fn throughput() {
+        let tree = Node::parse("AB&C|D^EF>=GH&!|AC^&BD|!|").unwrap();
+        let program = Program::compile(&tree);
+        let mut vm = Vm::new(&program);
+        let evaluations = 10_000_000u64;
+        let start = std::time::Instant::now();
+        let satisfied = (0..evaluations)
+            .filter(|row| vm.evaluate_row(row & 0xff))
+            .count();
+        let elapsed = start.elapsed();
+        println!(
+            "{satisfied} / {evaluations} in {elapsed:?}: {:.1}M evaluations/s",
+            evaluations as f64 / elapsed.as_secs_f64() / 1e6
+        );
+    }
+
+    #[test]
+    fn huge_formula() {
+        let tree = Node::parse(crate::bool_formula_ast::huge_formula()).unwrap();
+        let program = Program::compile(&tree);
+        assert_eq!(program.max_depth, 2);
+        let mut vm = Vm::new(&program);
+        assert!(vm.evaluate(&[true, true]));
+        assert!(!vm.evaluate(&[false, true]));
+    }
+}
@@ -0,0 +1,535 @@
+//! <https://en.wikipedia.org/wiki/Conflict-driven_clause_learning> on a [`Cnf`], see [`cdcl`].
+//!
+//! In the manner of MiniSat: two watched literals per clause, a clause learned at the first
+//! unique implication point of every conflict, VSIDS branching with saved phases,
+//! restarts following the Luby sequence and deletion of the least active learned clauses.
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::mem;
+
+use crate::clause::{Cnf, Literal};
+
+/// Conflicts before the first restart, multiplied by the Luby sequence
+const RESTART_BASE: u64 = 100;
+const VAR_DECAY: f64 = 0.95;
+const CLAUSE_DECAY: f64 = 0.999;
+
+/// `1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8, ...`
+fn luby(mut i: u64) -> u64 {
+    let (mut size, mut power) = (1, 1);
+    while size < i + 1 {
+        size = 2 * size + 1;
+        power *= 2;
+    }
+    while size - 1 != i {
+        size = (size - 1) / 2;
+        power /= 2;
+        i %= size;
+    }
+    power
+}
+
+struct ClauseData {
+    /// The first two are watched, the first is the one implied when the clause is a reason
+    literals: Vec<Literal>,
+    learned: bool,
+    activity: f64,
+    deleted: bool,
+}
+
+struct Solver {
+    clauses: Vec<ClauseData>,
+    /// Clauses watching the negation of each literal, visited when the literal becomes true
+    watches: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    levels: Vec<usize>,
+    /// Clause that implied each variable, `None` for decisions
+    reasons: Vec<Option<usize>>,
+    trail: Vec<Literal>,
+    /// Trail length at the start of each decision level
+    trail_limits: Vec<usize>,
+    /// Trail position of the next literal to propagate
+    propagated: usize,
+    activities: Vec<f64>,
+    var_increment: f64,
+    clause_increment: f64,
+    /// Unassigned variables by activity, entries whose activity changed are skipped
+    order: BinaryHeap<(u64, Reverse<usize>)>,
+    /// Last value of each variable, tried first when branching on it
+    phases: Vec<bool>,
+    /// Variables in the clause being learned
+    seen: Vec<bool>,
+    max_learned: usize,
+    /// The formula has an empty clause or contradictory units
+    contradiction: bool,
+}
+
+impl Solver {
+    fn new(cnf: &Cnf) -> Self {
+        let nb_vars = cnf.variables().len();
+        let mut solver = Self {
+            clauses: Vec::new(),
+            watches: vec![Vec::new(); 2 * nb_vars],
+            values: vec![None; nb_vars],
+            levels: vec![0; nb_vars],
+            reasons: vec![None; nb_vars],
+            trail: Vec::new(),
+            trail_limits: Vec::new(),
+            propagated: 0,
+            activities: vec![0.0; nb_vars],
+            var_increment: 1.0,
+            clause_increment: 1.0,
+            order: (0..nb_vars).map(|var| (0, Reverse(var))).collect(),
+            phases: vec![false; nb_vars],
+            seen: vec![false; nb_vars],
+            max_learned: cnf.clauses().len() / 3 + 1000,
+            contradiction: false,
+        };
+        for clause in cnf.clauses() {
+            if clause.is_tautology() {
+                continue;
+            }
+            match clause.literals() {
+                [] => solver.contradiction = true,
+                [unit] => match solver.value(*unit) {
+                    None => solver.enqueue(*unit, None),
+                    Some(true) => (),
+                    Some(false) => solver.contradiction = true,
+                },
+                literals => {
+                    solver.attach(literals.to_vec(), false);
+                }
+            }
+        }
+        solver
+    }
+
+    fn value(&self, literal: Literal) -> Option<bool> {
+        self.values[literal.var()].map(|value| value == literal.is_positive())
+    }
+
+    fn level(&self) -> usize {
+        self.trail_limits.len()
+    }
+
+    fn enqueue(&mut self, literal: Literal, reason: Option<usize>) {
+        let var = literal.var();
+        self.values[var] = Some(literal.is_positive());
+        self.levels[var] = self.level();
+        self.reasons[var] = reason;
+        self.trail.push(literal);
+    }
+
+    /// Adds a clause of at least 2 literals and watches its first two
+    fn attach(&mut self, literals: Vec<Literal>, learned: bool) -> usize {
+        let index = self.clauses.len();
+        self.watches[(!literals[0]).index()].push(index);
+        self.watches[(!literals[1]).index()].push(index);
+        self.clauses.push(ClauseData {
+            literals,
+            learned,
+            activity: 0.0,
+            deleted: false,
+        });
+        index
+    }
+
+    /// Propagates the trail, returns a clause whose literals are all false if there is one
+    fn propagate(&mut self) -> Option<usize> {
+        while self.propagated < self.trail.len() {
+            let literal = self.trail[self.propagated];
+            self.propagated += 1;
+            let false_literal = !literal;
+            let mut watchers = mem::take(&mut self.watches[literal.index()]);
+            let mut kept = 0;
+            let mut conflict = None;
+            let mut i = 0;
+            while i < watchers.len() {
+                let index = watchers[i];
+                i += 1;
+                let clause = &mut self.clauses[index];
+                if clause.deleted {
+                    continue;
+                }
+                let literals = &mut clause.literals;
+                if literals[0] == false_literal {
+                    literals.swap(0, 1);
+                }
+                let first = literals[0];
+                let value = |literal: Literal| {
+                    self.values[literal.var()].map(|value| value == literal.is_positive())
+                };
+                if value(first) == Some(true) {
+                    watchers[kept] = index;
+                    kept += 1;
+                    continue;
+                }
+                // Watch another literal that isn't false
+                if let Some(k) = (2..literals.len()).find(|&k| value(literals[k]) != Some(false)) {
+                    literals.swap(1, k);
+                    self.watches[(!literals[1]).index()].push(index);
+                    continue;
+                }
+                watchers[kept] = index;
+                kept += 1;
+                if value(first) == Some(false) {
+                    conflict = Some(index);
+                    // Keep the remaining watchers
+                    while i < watchers.len() {
+                        watchers[kept] = watchers[i];
+                        kept += 1;
+                        i += 1;
+                    }
+                } else {
+                    self.enqueue(first, Some(index));
+                }
+            }
+            watchers.truncate(kept);
+            self.watches[literal.index()] = watchers;
+            if conflict.is_some() {
+                return conflict;
+            }
+        }
+        None
+    }
+
+    fn bump_var(&mut self, var: usize) {
+        self.activities[var] += self.var_increment;
+        if self.activities[var] > 1e100 {
+            self.activities
+                .iter_mut()
+                .for_each(|activity| *activity *= 1e-100);
+            self.var_increment *= 1e-100;
+            self.rebuild_order();
+        }
+    }
+
+    fn bump_clause(&mut self, index: usize) {
+        self.clauses[index].activity += self.clause_increment;
+        if self.clauses[index].activity > 1e20 {
+            for clause in self.clauses.iter_mut().filter(|clause| clause.learned) {
+                clause.activity *= 1e-20;
+            }
+            self.clause_increment *= 1e-20;
+        }
+    }
+
+    fn push_order(&mut self, var: usize) {
+        self.order
+            .push((self.activities[var].to_bits(), Reverse(var)));
+    }
+
+    /// Drops the skipped entries of `order`
+    fn rebuild_order(&mut self) {
+        self.order.clear();
+        for var in 0..self.values.len() {
+            if self.values[var].is_none() {
+                self.push_order(var);
+            }
+        }
+    }
+
+    /// Clause learned from the conflict, its first literal is the negation of the first
+    /// unique implication point and its second is of the level to backtrack to.
+    fn analyze(&mut self, conflict: usize) -> (Vec<Literal>, usize) {
+        let mut learned = vec![Literal::new(0, true)];
+        // Literals of the current level still to resolve
+        let mut pending = 0;
+        let mut index = self.trail.len();
+        let mut clause = conflict;
+        let mut implied = None;
+        loop {
+            if self.clauses[clause].learned {
+                self.bump_clause(clause);
+            }
+            // The first literal of a reason is the one it implied
+            let skip = implied.is_some() as usize;
+            for i in skip..self.clauses[clause].literals.len() {
+                let literal = self.clauses[clause].literals[i];
+                let var = literal.var();
+                if self.seen[var] || self.levels[var] == 0 {
+                    continue;
+                }
+                self.seen[var] = true;
+                self.bump_var(var);
+                if self.levels[var] == self.level() {
+                    pending += 1;
+                } else {
+                    learned.push(literal);
+                }
+            }
+            // Latest literal of the current level in the clause
+            let literal = loop {
+                index -= 1;
+                if self.seen[self.trail[index].var()] {
+                    break self.trail[index];
+                }
+            };
+            self.seen[literal.var()] = false;
+            pending -= 1;
+            implied = Some(literal);
+            if pending == 0 {
+                break;
+            }
+            clause = self.reasons[literal.var()].unwrap();
+        }
+        learned[0] = !implied.unwrap();
+        for literal in &learned[1..] {
+            self.seen[literal.var()] = false;
+        }
+
+        let mut level = 0;
+        if learned.len() > 1 {
+            let second = (1..learned.len())
+                .max_by_key(|&i| self.levels[learned[i].var()])
+                .unwrap();
+            learned.swap(1, second);
+            level = self.levels[learned[1].var()];
+        }
+        (learned, level)
+    }
+
+    fn backtrack(&mut self, level: usize) {
+        if self.level() <= level {
+            return;
+        }
+        let len = self.trail_limits[level];
+        for i in (len..self.trail.len()).rev() {
+            let var = self.trail[i].var();
+            self.phases[var] = self.values[var].unwrap();
+            self.values[var] = None;
+            self.reasons[var] = None;
+            self.push_order(var);
+        }
+        self.trail.truncate(len);
+        self.trail_limits.truncate(level);
+        self.propagated = len;
+        if self.order.len() > 4 * self.values.len() + 1000 {
+            self.rebuild_order();
+        }
+    }
+
+    /// The unassigned variable with the highest activity, with its saved phase
+    fn branch(&mut self) -> Option<Literal> {
+        while let Some((activity, Reverse(var))) = self.order.pop() {
+            if self.values[var].is_none() && activity == self.activities[var].to_bits() {
+                return Some(Literal::new(var, self.phases[var]));
+            }
+        }
+        None
+    }
+
+    /// Deletes the less active half of the learned clauses, except binary ones and reasons
+    fn reduce(&mut self) {
+        let locked = |solver: &Self, index: usize| {
+            let first = solver.clauses[index].literals[0];
+            solver.reasons[first.var()] == Some(index) && solver.value(first) == Some(true)
+        };
+        let mut learned = (0..self.clauses.len())
+            .filter(|&i| {
+                let clause = &self.clauses[i];
+                clause.learned && !clause.deleted && clause.literals.len() > 2
+            })
+            .filter(|&i| !locked(self, i))
+            .collect::<Vec<_>>();
+        learned.sort_by(|&a, &b| {
+            self.clauses[a]
+                .activity
+                .total_cmp(&self.clauses[b].activity)
+        });
+        for &index in &learned[..learned.len() / 2] {
+            let clause = &mut self.clauses[index];
+            clause.deleted = true;
+            clause.literals = Vec::new();
+        }
+    }
+
+    fn nb_learned(&self) -> usize {
+        self.clauses
+            .iter()
+            .filter(|clause| clause.learned && !clause.deleted)
+            .count()
+    }
+
+    fn solve(mut self) -> Option<Vec<bool>> {
+        if self.contradiction {
+            return None;
+        }
+        let (mut conflicts, mut restarts) = (0, 0);
+        let mut learned = self.nb_learned();
+        loop {
+            if let Some(conflict) = self.propagate() {
+                if self.level() == 0 {
+                    return None;
+                }
+                let (clause, level) = self.analyze(conflict);
+                self.backtrack(level);
+                let first = clause[0];
+                if clause.len() == 1 {
+                    self.enqueue(first, None);
+                } else {
+                    let index = self.attach(clause, true);
+                    self.bump_clause(index);
+                    self.enqueue(first, Some(index));
+                    learned += 1;
+                }
+                self.var_increment /= VAR_DECAY;
+                self.clause_increment /= CLAUSE_DECAY;
+                conflicts += 1;
+                continue;
+            }
+
+            if conflicts >= RESTART_BASE * luby(restarts) {
+                conflicts = 0;
+                restarts += 1;
+                self.backtrack(0);
+                continue;
+            }
+            if learned >= self.max_learned {
+                self.reduce();
+                learned = self.nb_learned();
+                self.max_learned += self.max_learned / 10;
+            }
+            let Some(decision) = self.branch() else {
+                break;
+            };
+            self.trail_limits.push(self.trail.len());
+            self.enqueue(decision, None);
+        }
+        Some(
+            self.values
+                .into_iter()
+                .map(|value| value.unwrap())
+                .collect(),
+        )
+    }
+}
+
+/// A model of `cnf` indexed like its variables, `None` if it is unsatisfiable.
+pub fn cdcl(cnf: &Cnf) -> Option<Vec<bool>> {
+    Solver::new(cnf).solve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_parallel::satisfiable;
+    use crate::clause::generators::{self, cnf};
+    use crate::dpll::dpll;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn luby_sequence() {
+        let sequence = (0..15).map(luby).collect::<Vec<_>>();
+        assert_eq!(sequence, [1, 1, 2, 1, 1, 2, 4, 1, 1, 2, 1, 1, 2, 4, 8]);
+    }
+
+    #[test]
+    fn examples() {
+        assert_eq!(cdcl(&cnf(0, &[])), Some(vec![]));
+        assert_eq!(cdcl(&cnf(1, &[&[]])), None);
+        assert_eq!(cdcl(&cnf(1, &[&[1], &[-1]])), None);
+        assert_eq!(cdcl(&cnf(2, &[&[1, 2], &[-1], &[-2]])), None);
+        assert_eq!(
+            cdcl(&cnf(3, &[&[1, 2], &[-1, 3], &[-3]])),
+            Some(vec![false, true, false])
+        );
+        assert_eq!(cdcl(&cnf(2, &[&[1, -1], &[2]])), Some(vec![false, true]));
+    }
+
+    #[test]
+    fn pigeonhole() {
+        for holes in 1..=5 {
+            assert_eq!(cdcl(&generators::pigeonhole(holes + 1, holes)), None);
+            let fits = generators::pigeonhole(holes, holes);
+            assert!(fits.evaluate(&cdcl(&fits).unwrap()));
+        }
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..200 {
+            let nb_vars = rand::random::<usize>() % 10 + 3;
+            let nb_clauses = rand::random::<usize>() % (6 * nb_vars);
+            let cnf = generators::random_3sat(nb_vars, nb_clauses);
+            let model = cdcl(&cnf);
+            assert_eq!(model.is_some(), satisfiable(&cnf.to_node()), "{cnf}");
+            if let Some(model) = model {
+                assert!(cnf.evaluate(&model), "{cnf}");
+            }
+        }
+    }
+
+    #[test]
+    fn random_3sat() {
+        // Around the threshold, with enough conflicts to restart and delete learned clauses
+        for _ in 0..5 {
+            let cnf = generators::random_3sat(100, 426);
+            let model = cdcl(&cnf);
+            assert_eq!(model.is_some(), dpll(&cnf).is_some(), "{cnf}");
+            if let Some(model) = model {
+                assert!(cnf.evaluate(&model));
+            }
+        }
+    }
+
+    #[test]
+    fn learned_clause_deletion() {
+        let mut solver = Solver::new(&generators::pigeonhole(7, 6));
+        solver.max_learned = 10;
+        assert_eq!(solver.solve(), None);
+        for _ in 0..5 {
+            let cnf = generators::random_3sat(100, 400);
+            let mut solver = Solver::new(&cnf);
+            solver.max_learned = 10;
+            let model = solver.solve();
+            assert_eq!(model.is_some(), dpll(&cnf).is_some(), "{cnf}");
+            if let Some(model) = model {
+                assert!(cnf.evaluate(&model));
+            }
+        }
+    }
+
+    fn time(solver: fn(&Cnf) -> Option<Vec<bool>>, cnf: &Cnf) -> (bool, Duration) {
+        let start = Instant::now();
+        let satisfiable = solver(cnf).is_some();
+        (satisfiable, start.elapsed())
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release"]
+    fn benchmark_pigeonhole() {
+        for holes in 6..=8 {
+            let cnf = generators::pigeonhole(holes + 1, holes);
+            let (_, cdcl_time) = time(cdcl, &cnf);
+            let (_, dpll_time) = time(dpll, &cnf);
+            println!(
+                "{} pigeons, {holes} holes: CDCL {cdcl_time:?}, DPLL {dpll_time:?}",
+                holes + 1
+            );
+        }
+    }
+
+    #[test]
+    #[ignore = "benchmark, run with --release"]
+    fn benchmark_random_3sat() {
+        for nb_vars in [50, 100, 150] {
+            let instances = (0..10)
+                .map(|_| generators::random_3sat(nb_vars, nb_vars * 426 / 100))
+                .collect::<Vec<_>>();
+            let (mut cdcl_time, mut dpll_time, mut satisfiable) =
+                (Duration::ZERO, Duration::ZERO, 0);
+            for cnf in &instances {
+                let (sat, elapsed) = time(cdcl, cnf);
+                cdcl_time += elapsed;
+                satisfiable += sat as usize;
+                let (dpll_sat, elapsed) = time(dpll, cnf);
+                dpll_time += elapsed;
+                assert_eq!(sat, dpll_sat);
+            }
+            println!(
+                "{nb_vars} variables: {satisfiable}/10 satisfiable, CDCL {cdcl_time:?}, DPLL {dpll_time:?}"
+            );
+        }
+    }
+}
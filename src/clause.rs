@@ -0,0 +1,507 @@
+//! Formulas in conjunctive normal form as lists of clauses, see [`Cnf`].
+//!
+//! Variables are numbered from `1` as in DIMACS, a literal is the number of its variable,
+//! negated if the variable is.
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::ops::Not;
+
+use crate::bool_formula_ast::{Node, Oper};
+use crate::variable::Var;
+
+/// A variable (positive) or its negation (negative), never `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Literal(i32);
+
+impl Literal {
+    /// Literal of the `var`th (from `0`) variable of a [`Cnf`]
+    pub fn new(var: usize, positive: bool) -> Self {
+        let number = i32::try_from(var + 1).expect("too many variables");
+        Self(if positive { number } else { -number })
+    }
+
+    /// # Panics
+    ///
+    /// If `value` is `0`.
+    pub fn from_signed(value: i32) -> Self {
+        assert_ne!(value, 0, "0 is not a literal");
+        Self(value)
+    }
+
+    pub fn signed(self) -> i32 {
+        self.0
+    }
+
+    /// Index of the variable, from `0`
+    pub fn var(self) -> usize {
+        self.0.unsigned_abs() as usize - 1
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+
+    /// Index in per literal tables, `2 * var` for the negative literal and the next one for the positive
+    pub fn index(self) -> usize {
+        2 * self.var() + self.is_positive() as usize
+    }
+}
+
+impl Not for Literal {
+    type Output = Self;
+
+    fn not(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// `|` of literals, sorted and without duplicates. The empty clause is false.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Clause(Vec<Literal>);
+
+impl Clause {
+    pub fn new(literals: impl IntoIterator<Item = Literal>) -> Self {
+        let mut literals = literals.into_iter().collect::<Vec<_>>();
+        literals.sort_unstable();
+        literals.dedup();
+        Self(literals)
+    }
+
+    pub fn literals(&self) -> &[Literal] {
+        &self.0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Contains a literal and its negation, so is always true
+    pub fn is_tautology(&self) -> bool {
+        self.0
+            .iter()
+            .any(|&literal| literal.is_positive() && self.0.binary_search(&!literal).is_ok())
+    }
+
+    /// Every literal of `self` is in `other`, which is then implied by `self`
+    pub fn subsumes(&self, other: &Clause) -> bool {
+        let mut others = other.0.iter();
+        self.0
+            .iter()
+            .all(|literal| others.by_ref().any(|other| other == literal))
+    }
+
+    /// Value under `values`, indexed by variable
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        self.0
+            .iter()
+            .any(|literal| values[literal.var()] == literal.is_positive())
+    }
+}
+
+impl fmt::Display for Clause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let literals = self.0.iter().map(Literal::to_string).collect::<Vec<_>>();
+        write!(f, "({})", literals.join(" "))
+    }
+}
+
+/// `&` of clauses over named variables. Without clauses it is true.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Cnf {
+    variables: Vec<Var>,
+    clauses: Vec<Clause>,
+}
+
+impl Cnf {
+    pub fn new(variables: Vec<Var>) -> Self {
+        Self {
+            variables,
+            clauses: Vec::new(),
+        }
+    }
+
+    /// `node` MUST be in conjunctive normal form, see [`Node::is_conjunctive_normal_form`]
+    ///
+    /// Literals are numbered in the order of [`Node::variables`].
+    pub fn from_node(node: &Node) -> Self {
+        let mut cnf = Self::new(node.variables());
+        let indices = cnf
+            .variables
+            .iter()
+            .enumerate()
+            .map(|(i, &var)| (var, i))
+            .collect::<HashMap<_, _>>();
+        let mut flat = node.clone();
+        flat.flatten_mut();
+        let mut clauses = Vec::new();
+        for clause in flat.into_operands(Oper::Conjunction) {
+            let mut literals = Vec::new();
+            let mut satisfied = false;
+            for operand in clause.into_operands(Oper::Disjunction) {
+                let (mut operand, mut positive) = (&operand, true);
+                while let Node::Neg(child) = operand {
+                    (operand, positive) = (child, !positive);
+                }
+                match operand {
+                    Node::Value(value) => satisfied |= *value == positive,
+                    Node::Variable(var) => literals.push(Literal::new(indices[var], positive)),
+                    Node::Neg(_) | Node::Operator(_) => {
+                        panic!("not in conjunctive normal form: {node}")
+                    }
+                }
+            }
+            if !satisfied {
+                clauses.push(Clause::new(literals));
+            }
+        }
+        cnf.clauses = clauses;
+        cnf
+    }
+
+    /// `&` of `|` of literals, `1` without clauses and `0` for the empty clause
+    pub fn to_node(&self) -> Node {
+        if self.clauses.is_empty() {
+            return Node::Value(true);
+        }
+        let clauses = self
+            .clauses
+            .iter()
+            .map(|clause| {
+                if clause.is_empty() {
+                    return Node::Value(false);
+                }
+                let literals = clause
+                    .literals()
+                    .iter()
+                    .map(|&literal| {
+                        let var = Node::Variable(self.variables[literal.var()]);
+                        if literal.is_positive() {
+                            var
+                        } else {
+                            Node::Neg(Box::new(var))
+                        }
+                    })
+                    .collect();
+                Node::n_ary(Oper::Disjunction, literals)
+            })
+            .collect();
+        Node::n_ary(Oper::Conjunction, clauses)
+    }
+
+    pub fn variables(&self) -> &[Var] {
+        &self.variables
+    }
+
+    pub fn clauses(&self) -> &[Clause] {
+        &self.clauses
+    }
+
+    /// Index of `var` in the variables, panics if it isn't one
+    pub fn index(&self, var: Var) -> usize {
+        self.variables
+            .iter()
+            .position(|&v| v == var)
+            .unwrap_or_else(|| panic!("{var} is not a variable of the formula"))
+    }
+
+    /// # Panics
+    ///
+    /// If a literal of `clause` isn't a variable of `self`.
+    pub fn push(&mut self, clause: Clause) {
+        if let Some(literal) = clause
+            .literals()
+            .iter()
+            .find(|l| l.var() >= self.variables.len())
+        {
+            panic!("{literal} is not a variable of the formula");
+        }
+        self.clauses.push(clause);
+    }
+
+    /// Value under `values`, indexed by variable
+    pub fn evaluate(&self, values: &[bool]) -> bool {
+        self.clauses.iter().all(|clause| clause.evaluate(values))
+    }
+
+    /// Keeps the first of equal clauses
+    pub fn dedup(&mut self) {
+        let mut seen = HashSet::new();
+        self.clauses.retain(|clause| seen.insert(clause.clone()));
+    }
+
+    pub fn remove_tautologies(&mut self) {
+        self.clauses.retain(|clause| !clause.is_tautology());
+    }
+
+    /// Removes the clauses implied by a smaller one, and all but the first of equal clauses.
+    ///
+    /// Every clause is only compared to those sharing its rarest literal.
+    pub fn remove_subsumed(&mut self) {
+        let mut occurrences = vec![Vec::new(); 2 * self.variables.len()];
+        for (i, clause) in self.clauses.iter().enumerate() {
+            for &literal in clause.literals() {
+                occurrences[literal.index()].push(i);
+            }
+        }
+
+        let mut order = (0..self.clauses.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&i| self.clauses[i].len());
+        let mut removed = vec![false; self.clauses.len()];
+        for i in order {
+            let clause = &self.clauses[i];
+            if removed[i] {
+                continue;
+            }
+            let Some(rarest) = clause
+                .literals()
+                .iter()
+                .map(|&literal| &occurrences[literal.index()])
+                .min_by_key(|occurrences| occurrences.len())
+            else {
+                // The empty clause subsumes everything
+                removed.iter_mut().for_each(|removed| *removed = true);
+                removed[i] = false;
+                break;
+            };
+            for &j in rarest {
+                let other = &self.clauses[j];
+                let equal = other.len() == clause.len();
+                if j != i && !removed[j] && !(equal && j < i) && clause.subsumes(other) {
+                    removed[j] = true;
+                }
+            }
+        }
+        let mut removed = removed.into_iter();
+        self.clauses.retain(|_| !removed.next().unwrap());
+    }
+
+    /// Removes tautologies and subsumed clauses, the formula stays equivalent
+    pub fn simplify(&mut self) {
+        self.remove_tautologies();
+        self.remove_subsumed();
+    }
+}
+
+impl fmt::Display for Cnf {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let clauses = self
+            .clauses
+            .iter()
+            .map(Clause::to_string)
+            .collect::<Vec<_>>();
+        write!(f, "{}", clauses.join(" "))
+    }
+}
+
+/// Formulas to test and benchmark solvers on
+#[cfg(test)]
+pub(crate) mod generators {
+    use rand::seq::index::sample;
+
+    use super::*;
+
+    /// Clauses of DIMACS literals over `x1`, ..., `x{nb_vars}`
+    pub fn cnf(nb_vars: usize, clauses: &[&[i32]]) -> Cnf {
+        let variables = (1..=nb_vars).map(|i| Var::new(&format!("x{i}"))).collect();
+        let mut cnf = Cnf::new(variables);
+        for literals in clauses {
+            cnf.push(Clause::new(
+                literals
+                    .iter()
+                    .map(|&literal| Literal::from_signed(literal)),
+            ));
+        }
+        cnf
+    }
+
+    /// `pigeons` in `holes` with at most one pigeon per hole, unsatisfiable if there are more pigeons.
+    ///
+    /// Hard for resolution, so for DPLL and CDCL too.
+    pub fn pigeonhole(pigeons: usize, holes: usize) -> Cnf {
+        let variables = (0..pigeons)
+            .flat_map(|pigeon| (0..holes).map(move |hole| Var::new(&format!("p{pigeon}_{hole}"))))
+            .collect();
+        let mut cnf = Cnf::new(variables);
+        let var = |pigeon: usize, hole: usize| pigeon * holes + hole;
+        for pigeon in 0..pigeons {
+            cnf.push(Clause::new(
+                (0..holes).map(|hole| Literal::new(var(pigeon, hole), true)),
+            ));
+        }
+        for hole in 0..holes {
+            for a in 0..pigeons {
+                for b in a + 1..pigeons {
+                    cnf.push(Clause::new([
+                        Literal::new(var(a, hole), false),
+                        Literal::new(var(b, hole), false),
+                    ]));
+                }
+            }
+        }
+        cnf
+    }
+
+    /// Clauses of 3 distinct variables with random signs, hardest around 4.26 clauses per variable.
+    pub fn random_3sat(nb_vars: usize, nb_clauses: usize) -> Cnf {
+        let variables = (1..=nb_vars).map(|i| Var::new(&format!("x{i}"))).collect();
+        let mut cnf = Cnf::new(variables);
+        let mut rng = rand::thread_rng();
+        for _ in 0..nb_clauses {
+            let vars = sample(&mut rng, nb_vars, 3);
+            cnf.push(Clause::new(
+                vars.into_iter()
+                    .map(|var| Literal::new(var, rand::random())),
+            ));
+        }
+        cnf
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ex04_truth_table::TruthTable;
+
+    fn clause(literals: &[i32]) -> Clause {
+        Clause::new(literals.iter().map(|&l| Literal::from_signed(l)))
+    }
+
+    #[test]
+    fn literals() {
+        let a = Literal::new(0, true);
+        assert_eq!(a.signed(), 1);
+        assert_eq!((!a).signed(), -1);
+        assert_eq!(Literal::from_signed(-3).var(), 2);
+        assert!(!Literal::from_signed(-3).is_positive());
+        assert_eq!(
+            [-1, 1, -2, 2].map(|l| Literal::from_signed(l).index()),
+            [0, 1, 2, 3]
+        );
+
+        assert_eq!(clause(&[3, -1, 3, 2]).to_string(), "(-1 2 3)");
+        assert!(clause(&[1, -2, 2]).is_tautology());
+        assert!(!clause(&[1, 2, -3]).is_tautology());
+        assert!(clause(&[-1, 3]).subsumes(&clause(&[-1, 2, 3])));
+        assert!(!clause(&[-1, 3]).subsumes(&clause(&[1, 2, 3])));
+        assert!(Clause::default().subsumes(&clause(&[1])));
+    }
+
+    #[test]
+    fn from_node() {
+        let node = Node::parse("AB!|C|B!A|&1D|&A0|&").unwrap();
+        let cnf = Cnf::from_node(&node);
+        assert_eq!(cnf.variables(), ['A', 'B', 'C', 'D'].map(Var::from));
+        assert_eq!(cnf.to_string(), "(-2 1 3) (-2 1) (1)");
+        assert_eq!(cnf.to_node().to_string(), "B!AC||B!A|A&&");
+
+        assert_eq!(
+            Cnf::from_node(&Node::Value(true)).to_node(),
+            Node::Value(true)
+        );
+        let never = Cnf::from_node(&Node::parse("A0&").unwrap());
+        assert_eq!(never.to_string(), "(1) ()");
+        assert_eq!(never.to_node().to_string(), "A0&");
+    }
+
+    #[test]
+    fn many_variables() {
+        // x0 > x1 > ... > x32767
+        let n = 32_768;
+        let chain = (1..n)
+            .map(|i| format!("[x{}]![x{i}]|", i - 1))
+            .collect::<String>();
+        let node = Node::parse(format!("{chain}{}", "&".repeat(n - 2))).unwrap();
+        let cnf = Cnf::from_node(&node);
+        assert_eq!(cnf.variables().len(), n);
+        assert_eq!(cnf.clauses().len(), n - 1);
+        assert_eq!(cnf.clauses()[0].to_string(), "(-1 2)");
+    }
+
+    #[test]
+    #[should_panic(expected = "not in conjunctive normal form")]
+    fn not_cnf() {
+        Cnf::from_node(&Node::parse("AB&C|").unwrap());
+    }
+
+    #[test]
+    fn simplify() {
+        let vars = ['A', 'B', 'C', 'D'].map(Var::from).to_vec();
+        let mut cnf = Cnf::new(vars);
+        for literals in [
+            &[1, 2, 3][..],
+            &[-1, 1, 4],
+            &[2, 1],
+            &[3, 4],
+            &[1, 2],
+            &[4, 3, -2],
+        ] {
+            cnf.push(clause(literals));
+        }
+        let mut deduped = cnf.clone();
+        deduped.dedup();
+        assert_eq!(deduped.clauses().len(), 5);
+        let mut without_tautologies = cnf.clone();
+        without_tautologies.remove_tautologies();
+        assert_eq!(without_tautologies.clauses().len(), 5);
+
+        cnf.simplify();
+        assert_eq!(cnf.to_string(), "(1 2) (3 4)");
+        cnf.push(Clause::default());
+        cnf.remove_subsumed();
+        assert_eq!(cnf.to_string(), "()");
+    }
+
+    #[test]
+    #[should_panic(expected = "is not a variable")]
+    fn push_unknown_variable() {
+        Cnf::new(vec![Var::from('A')]).push(clause(&[2]));
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..100 {
+            let mut tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            tree.to_primitive_connectives_mut();
+            tree.to_negation_normal_form_mut();
+            if tree.to_string().len() > 100 {
+                // That's a tad big
+                continue;
+            }
+            tree.to_conjunctive_normal_form_mut();
+
+            let mut cnf = Cnf::from_node(&tree);
+            let table = TruthTable::from_node(&tree).unwrap();
+            cnf.simplify();
+            let clauses = cnf.clauses();
+            for (i, clause) in clauses.iter().enumerate() {
+                assert!(!clause.is_tautology(), "{tree}");
+                assert!(
+                    !clauses
+                        .iter()
+                        .enumerate()
+                        .any(|(j, other)| i != j && other.subsumes(clause)),
+                    "{tree}"
+                );
+            }
+            for row in table.entries() {
+                let values = row.values().map(|(_, value)| value).collect::<Vec<_>>();
+                assert_eq!(cnf.evaluate(&values), row.result(), "{tree}");
+            }
+            let simplified = TruthTable::from_node(&cnf.to_node())
+                .unwrap()
+                .with_variables(table.variables().to_vec())
+                .unwrap();
+            assert_eq!(simplified, table, "{tree}");
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Product terms over the variables of a [`TruthTable`](crate::ex04_truth_table::TruthTable).
+//!
+//! ```text
+//! A!C& over A, B, C  ->  1-0
+//! ```
+use crate::bool_formula_ast::{Node, Oper};
+use crate::variable::Var;
+
+/// `&` of literals, with the bit layout of the row indices of a truth table:
+/// the first of `n` variables is bit `n - 1`.
+///
+/// A variable is in the cube when its bit is set in `mask`, negated when it's cleared in `bits`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Cube {
+    bits: u64,
+    mask: u64,
+}
+
+impl Cube {
+    /// Bits of `bits` outside of `mask` are ignored.
+    pub fn new(bits: u64, mask: u64) -> Self {
+        Self {
+            bits: bits & mask,
+            mask,
+        }
+    }
+
+    /// Cube of `row` only, `nb_vars` literals
+    pub fn minterm(row: u64, nb_vars: usize) -> Self {
+        Self::new(row, Self::all(nb_vars))
+    }
+
+    /// Cube of every row, no literals
+    pub fn universe() -> Self {
+        Self::new(0, 0)
+    }
+
+    /// Mask of the bits of `nb_vars` variables
+    fn all(nb_vars: usize) -> u64 {
+        assert!(nb_vars <= 64, "more than 64 variables");
+        u64::MAX.checked_shr(64 - nb_vars as u32).unwrap_or(0)
+    }
+
+    pub fn bits(&self) -> u64 {
+        self.bits
+    }
+
+    pub fn mask(&self) -> u64 {
+        self.mask
+    }
+
+    pub fn literals(&self) -> u32 {
+        self.mask.count_ones()
+    }
+
+    pub fn contains(&self, row: u64) -> bool {
+        row & self.mask == self.bits
+    }
+
+    /// Whether every row of `other` is in `self`
+    pub fn covers(&self, other: &Cube) -> bool {
+        other.mask & self.mask == self.mask && other.bits & self.mask == self.bits
+    }
+
+    /// Cube of the rows of both cubes if it exists, when they only differ by
+    /// the value of one variable: `AB!&` and `AB&` give `A`.
+    pub fn merge(&self, other: &Cube) -> Option<Cube> {
+        let diff = self.bits ^ other.bits;
+        (self.mask == other.mask && diff.count_ones() == 1)
+            .then(|| Cube::new(self.bits, self.mask & !diff))
+    }
+
+    /// Cube of the rows in both cubes, `None` if they have none in common
+    pub fn intersection(&self, other: &Cube) -> Option<Cube> {
+        let both = self.mask & other.mask;
+        ((self.bits ^ other.bits) & both == 0)
+            .then(|| Cube::new(self.bits | other.bits, self.mask | other.mask))
+    }
+
+    /// Smallest cube holding both cubes
+    pub fn supercube(&self, other: &Cube) -> Cube {
+        Cube::new(
+            self.bits,
+            self.mask & other.mask & !(self.bits ^ other.bits),
+        )
+    }
+
+    /// Disjoint cubes of the rows of `self` that aren't in `other`
+    pub fn difference(&self, other: &Cube) -> Vec<Cube> {
+        if self.intersection(other).is_none() {
+            return vec![*self];
+        }
+        // Split `self` on the variables only `other` has, keeping the halves outside of it
+        let mut pieces = Vec::new();
+        let mut rest = *self;
+        for bit in (0..64).filter(|bit| (other.mask & !self.mask) >> bit & 1 == 1) {
+            let mask = rest.mask | 1 << bit;
+            pieces.push(Cube::new(rest.bits | !other.bits & 1 << bit, mask));
+            rest = Cube::new(rest.bits | other.bits & 1 << bit, mask);
+        }
+        pieces
+    }
+
+    /// `self` without the variable of `bit`
+    pub fn without(&self, bit: u32) -> Cube {
+        Cube::new(self.bits, self.mask & !(1 << bit))
+    }
+
+    /// Rows of the cube, among those of `nb_vars` variables
+    pub fn rows(&self, nb_vars: usize) -> impl Iterator<Item = u64> + use<> {
+        let free = Self::all(nb_vars) & !self.mask;
+        let bits = self.bits;
+        // Every subset of `free`, in increasing order
+        let mut subset = Some(0u64);
+        std::iter::from_fn(move || {
+            let current = subset?;
+            subset = (current != free).then(|| (current | !free).wrapping_add(1) & free);
+            Some(bits | current)
+        })
+    }
+
+    /// `1-0` for `A!C&` over `A, B, C`
+    pub fn pattern(&self, nb_vars: usize) -> String {
+        (0..nb_vars as u32)
+            .rev()
+            .map(|bit| match (self.mask >> bit & 1, self.bits >> bit & 1) {
+                (0, _) => '-',
+                (_, 0) => '0',
+                _ => '1',
+            })
+            .collect()
+    }
+
+    /// `&` of the literals, `1` if there are none
+    pub fn to_node(&self, variables: &[Var]) -> Node {
+        let literals = variables
+            .iter()
+            .enumerate()
+            .map(|(i, &var)| (var, (variables.len() - 1 - i) as u32))
+            .filter(|&(_, bit)| self.mask >> bit & 1 == 1)
+            .map(|(var, bit)| {
+                if self.bits >> bit & 1 == 1 {
+                    Node::Variable(var)
+                } else {
+                    Node::Neg(Box::new(Node::Variable(var)))
+                }
+            })
+            .collect::<Vec<_>>();
+        if literals.is_empty() {
+            return Node::Value(true);
+        }
+        Node::n_ary(Oper::Conjunction, literals)
+    }
+}
+
+/// `|` of the cubes, `0` if there are none
+pub fn sum_of_products(cubes: &[Cube], variables: &[Var]) -> Node {
+    if cubes.is_empty() {
+        return Node::Value(false);
+    }
+    Node::n_ary(
+        Oper::Disjunction,
+        cubes.iter().map(|cube| cube.to_node(variables)).collect(),
+    )
+}
+
+/// Disjoint cubes of the rows in none of `cubes`.
+///
+/// Splits the space on the variable most cubes have until a part is in a cube or in none.
+pub fn complement(cubes: &[Cube]) -> Vec<Cube> {
+    let mut complement = Vec::new();
+    // Parts of the space, with the cubes intersecting them
+    let mut stack = vec![(Cube::universe(), cubes.to_vec())];
+    while let Some((part, cubes)) = stack.pop() {
+        if cubes.is_empty() {
+            complement.push(part);
+            continue;
+        }
+        if cubes.iter().any(|cube| cube.covers(&part)) {
+            continue;
+        }
+        // No cube covers the part, so they all have a variable it doesn't
+        let bit = (0..64)
+            .filter(|bit| part.mask >> bit & 1 == 0)
+            .max_by_key(|bit| {
+                cubes
+                    .iter()
+                    .filter(|cube| cube.mask >> bit & 1 == 1)
+                    .count()
+            })
+            .unwrap();
+        for value in [0, 1] {
+            let half = Cube::new(part.bits | value << bit, part.mask | 1 << bit);
+            let inside = cubes
+                .iter()
+                .filter(|cube| cube.intersection(&half).is_some())
+                .copied()
+                .collect();
+            stack.push((half, inside));
+        }
+    }
+    complement
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cubes() {
+        let vars = ['A', 'B', 'C'].map(Var::from);
+        let cube = Cube::new(0b100, 0b101);
+        assert_eq!(cube.pattern(3), "1-0");
+        assert_eq!(cube.to_node(&vars).to_string(), "AC!&");
+        assert_eq!(cube.literals(), 2);
+        assert_eq!(cube.rows(3).collect::<Vec<_>>(), [0b100, 0b110]);
+        assert!(cube.contains(0b110));
+        assert!(!cube.contains(0b111));
+        assert!(cube.covers(&Cube::minterm(0b100, 3)));
+        assert!(cube.without(0).covers(&cube));
+        assert!(!cube.covers(&cube.without(0)));
+
+        let merged = Cube::minterm(0b010, 3).merge(&Cube::minterm(0b011, 3));
+        assert_eq!(merged, Some(Cube::new(0b010, 0b110)));
+        assert_eq!(
+            Cube::minterm(0b010, 3).merge(&Cube::minterm(0b001, 3)),
+            None
+        );
+        assert_eq!(cube.merge(&Cube::minterm(0b101, 3)), None);
+
+        // `1-0` and `-10`
+        let other = Cube::new(0b010, 0b011);
+        assert_eq!(cube.intersection(&other), Some(Cube::minterm(0b110, 3)));
+        assert_eq!(cube.intersection(&Cube::new(0b001, 0b001)), None);
+        assert_eq!(cube.supercube(&other), Cube::new(0b000, 0b001));
+        assert_eq!(cube.difference(&other), [Cube::minterm(0b100, 3)]);
+        assert_eq!(cube.difference(&Cube::universe()), []);
+        assert_eq!(cube.difference(&Cube::new(0b001, 0b001)), [cube]);
+        let pieces = Cube::universe().difference(&Cube::minterm(0b111, 3));
+        assert_eq!(pieces.iter().flat_map(|piece| piece.rows(3)).count(), 7);
+        assert!(pieces.iter().all(|piece| !piece.contains(0b111)));
+
+        // `A!C&` and `BC&` leave 4 rows
+        let rest = complement(&[cube, Cube::new(0b011, 0b011)]);
+        let mut rows = rest
+            .iter()
+            .flat_map(|cube| cube.rows(3))
+            .collect::<Vec<_>>();
+        rows.sort();
+        assert_eq!(rows, [0b000, 0b001, 0b010, 0b101]);
+        assert_eq!(complement(&[]), [Cube::universe()]);
+        assert_eq!(complement(&[Cube::universe()]), []);
+
+        assert_eq!(Cube::universe().rows(2).count(), 4);
+        assert_eq!(Cube::universe().to_node(&vars), Node::Value(true));
+        assert_eq!(Cube::minterm(0, 0).rows(0).collect::<Vec<_>>(), [0]);
+        assert_eq!(
+            sum_of_products(&[cube, Cube::new(0b011, 0b011)], &vars).to_string(),
+            "AC!&BC&|"
+        );
+        assert_eq!(sum_of_products(&[], &vars), Node::Value(false));
+    }
+}
@@ -0,0 +1,147 @@
+//! Locating errors inside formulas.
+//!
+//! Parsing errors carry the [`Span`] of the offending token, [`Diagnostic`]
+//! renders the formula with a caret underneath it:
+//!
+//! ```text
+//! trailing operands: formula has more than one root
+//!   AB&C
+//!   ^^^
+//! ```
+use std::fmt;
+
+/// Byte range `start..end` inside a formula.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    #[inline]
+    pub fn new(start: usize, end: usize) -> Self {
+        debug_assert!(start <= end);
+        Self { start, end }
+    }
+
+    /// Span of the character `char` found at byte `offset`.
+    #[inline]
+    pub fn of_char(offset: usize, char: char) -> Self {
+        Self::new(offset, offset + char.len_utf8())
+    }
+
+    /// Empty span, e.g. for the end of the formula.
+    #[inline]
+    pub fn at(offset: usize) -> Self {
+        Self::new(offset, offset)
+    }
+}
+
+/// Errors that can point at a location in the formula they were raised for.
+pub trait Spanned {
+    fn span(&self) -> Option<Span>;
+}
+
+/// Number of characters printed on each side of the span for long formulas.
+const CONTEXT: usize = 32;
+
+/// Displays `error` followed by `formula` with a caret under the error's span.
+///
+/// Long formulas are cut around the span.
+pub struct Diagnostic<'a, E> {
+    formula: &'a str,
+    error: &'a E,
+}
+
+impl<'a, E: Spanned + fmt::Display> Diagnostic<'a, E> {
+    pub fn new(formula: &'a str, error: &'a E) -> Self {
+        Self { formula, error }
+    }
+}
+
+impl<E: Spanned + fmt::Display> fmt::Display for Diagnostic<'_, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.error)?;
+
+        let Some(span) = self.error.span() else {
+            return writeln!(f, "  {}", self.formula);
+        };
+        let start = span.start.min(self.formula.len());
+        let end = span.end.min(self.formula.len());
+        // Work in characters, not bytes, so the caret lines up
+        let column = self.formula[..start].chars().count();
+        let width = self.formula[start..end].chars().count().max(1);
+        let total = self.formula.chars().count();
+
+        let first = column.saturating_sub(CONTEXT);
+        let last = total.min(column + width.min(2 * CONTEXT) + CONTEXT);
+        let (head, tail) = (
+            if first > 0 { "..." } else { "" },
+            if last < total { "..." } else { "" },
+        );
+        let excerpt = self
+            .formula
+            .chars()
+            .skip(first)
+            .take(last - first)
+            .collect::<String>();
+        let carets = width.min(last.max(column + 1) - column);
+
+        writeln!(f, "  {head}{excerpt}{tail}")?;
+        writeln!(
+            f,
+            "  {:pad$}{}{}",
+            "",
+            "^".repeat(carets),
+            if carets < width { "..." } else { "" },
+            pad = head.len() + column - first,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct TestError(Option<Span>);
+
+    impl fmt::Display for TestError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "oops")
+        }
+    }
+
+    impl Spanned for TestError {
+        fn span(&self) -> Option<Span> {
+            self.0
+        }
+    }
+
+    fn render(formula: &str, span: Option<Span>) -> String {
+        Diagnostic::new(formula, &TestError(span)).to_string()
+    }
+
+    #[test]
+    fn caret() {
+        assert_eq!(
+            render("AB&C", Some(Span::new(0, 3))),
+            "oops\n  AB&C\n  ^^^\n"
+        );
+        assert_eq!(render("AB&", Some(Span::at(3))), "oops\n  AB&\n     ^\n");
+        assert_eq!(render("A∧b", Some(Span::new(4, 5))), "oops\n  A∧b\n    ^\n");
+        assert_eq!(render("AB&", None), "oops\n  AB&\n");
+    }
+
+    #[test]
+    fn long_formula() {
+        let formula = format!("{}?{}", "A".repeat(100), "B".repeat(100));
+        let rendered = render(&formula, Some(Span::of_char(100, '?')));
+        let lines = rendered.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[1],
+            format!("  ...{}?{}...", "A".repeat(CONTEXT), "B".repeat(CONTEXT))
+        );
+        assert_eq!(lines[2], format!("  {}^", " ".repeat(3 + CONTEXT)));
+    }
+}
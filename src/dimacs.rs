@@ -0,0 +1,361 @@
+//! DIMACS CNF, the input format of most SAT solvers, see [`Cnf::to_dimacs`].
+//!
+//! ```text
+//! c 1 A
+//! c 2 B
+//! p cnf 2 2
+//! 1 -2 0
+//! 2 0
+//! ```
+//!
+//! Comments `c <number> <name>` give the names of the variables, the others are numbered `x1`, `x2`, ...
+use std::collections::HashSet;
+use std::fmt::Write;
+
+use thiserror::Error;
+
+use crate::clause::{Clause, Cnf, Literal};
+use crate::diagnostic::{Span, Spanned};
+use crate::variable::Var;
+
+#[derive(Error, Debug)]
+pub enum DimacsError {
+    #[error("missing `p cnf` header")]
+    MissingHeader(Span),
+    #[error("invalid header: {0}")]
+    InvalidHeader(String, Span),
+    #[error("unexpected '{0}'")]
+    InvalidLiteral(String, Span),
+    #[error("literal {0} is out of range, there are {1} variables")]
+    LiteralOutOfRange(i64, usize, Span),
+    #[error("clause is not terminated by `0`")]
+    UnterminatedClause(Span),
+    #[error("the header announces {0} clauses, found {1}")]
+    ClauseCount(usize, usize, Span),
+}
+
+impl Spanned for DimacsError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::MissingHeader(span)
+            | Self::InvalidHeader(_, span)
+            | Self::InvalidLiteral(_, span)
+            | Self::LiteralOutOfRange(_, _, span)
+            | Self::UnterminatedClause(span)
+            | Self::ClauseCount(_, _, span) => Some(*span),
+        }
+    }
+}
+
+/// Words of `line` along with their span, `offset` is the position of `line` in the text
+fn words(line: &str, offset: usize) -> impl Iterator<Item = (&str, Span)> {
+    line.split(|c: char| c.is_ascii_whitespace())
+        .filter(|word| !word.is_empty())
+        .map(move |word| {
+            let start = offset + (word.as_ptr() as usize - line.as_ptr() as usize);
+            (word, Span::new(start, start + word.len()))
+        })
+}
+
+/// `p cnf <variables> <clauses>`, returns the counts and the span of the number of clauses
+fn header(line: &str, offset: usize) -> Result<(usize, usize, Span), DimacsError> {
+    let mut words = words(line, offset).skip(1);
+    let line_span = Span::new(offset, offset + line.trim_end().len());
+    match words.next() {
+        Some(("cnf", _)) => (),
+        Some((word, span)) => {
+            return Err(DimacsError::InvalidHeader(
+                format!("expected `cnf`, found `{word}`"),
+                span,
+            ));
+        }
+        None => {
+            return Err(DimacsError::InvalidHeader(
+                "expected `cnf`".to_string(),
+                Span::at(line_span.end),
+            ));
+        }
+    }
+    let mut count = |what: &str| match words.next() {
+        Some((word, span)) => match word.parse::<usize>() {
+            Ok(count) => Ok((count, span)),
+            Err(_) => Err(DimacsError::InvalidHeader(
+                format!("`{word}` is not a number of {what}"),
+                span,
+            )),
+        },
+        None => Err(DimacsError::InvalidHeader(
+            format!("missing the number of {what}"),
+            Span::at(line_span.end),
+        )),
+    };
+
+    let (nb_vars, vars_span) = count("variables")?;
+    let (nb_clauses, clauses_span) = count("clauses")?;
+    if let Some((word, span)) = words.next() {
+        return Err(DimacsError::InvalidHeader(
+            format!("unexpected `{word}`"),
+            span,
+        ));
+    }
+    if i32::try_from(nb_vars).is_err() {
+        return Err(DimacsError::InvalidHeader(
+            format!("{nb_vars} variables are too many"),
+            vars_span,
+        ));
+    }
+    Ok((nb_vars, nb_clauses, clauses_span))
+}
+
+impl Cnf {
+    /// DIMACS CNF of `self`, with a comment naming each variable
+    pub fn to_dimacs(&self) -> String {
+        let mut dimacs = String::new();
+        for (i, var) in self.variables().iter().enumerate() {
+            writeln!(dimacs, "c {} {var}", i + 1).unwrap();
+        }
+        let (nb_vars, nb_clauses) = (self.variables().len(), self.clauses().len());
+        writeln!(dimacs, "p cnf {nb_vars} {nb_clauses}").unwrap();
+        for clause in self.clauses() {
+            for literal in clause.literals() {
+                write!(dimacs, "{literal} ").unwrap();
+            }
+            dimacs.push_str("0\n");
+        }
+        dimacs
+    }
+
+    /// Parses DIMACS CNF, the spans of errors are byte ranges of `dimacs`.
+    ///
+    /// Clauses may span several lines, a line starting with `%` ends the input as in SATLIB benchmarks.
+    pub fn from_dimacs(dimacs: &str) -> Result<Self, DimacsError> {
+        let mut names = Vec::new();
+        let mut header_info = None;
+        let mut clauses = Vec::new();
+        let mut clause = Vec::new();
+        let mut last_literal = Span::at(0);
+
+        let mut offset = 0;
+        for line in dimacs.split_inclusive('\n') {
+            let line_offset = offset;
+            offset += line.len();
+            let mut line_words = words(line, line_offset).peekable();
+            match line_words.peek() {
+                None => continue,
+                Some((word, _)) if word.starts_with('c') => {
+                    if let (Some((number, _)), Some((name, _)), None) =
+                        (line_words.nth(1), line_words.next(), line_words.next())
+                        && let Ok(number) = number.parse::<usize>()
+                        && Var::is_valid_name(name)
+                    {
+                        names.push((number, Var::new(name)));
+                    }
+                    continue;
+                }
+                Some((word, _)) if word.starts_with('%') => break,
+                Some(("p", span)) => {
+                    if header_info.is_some() {
+                        return Err(DimacsError::InvalidHeader(
+                            "duplicate header".to_string(),
+                            *span,
+                        ));
+                    }
+                    header_info = Some(header(line, line_offset)?);
+                    continue;
+                }
+                Some(_) => (),
+            }
+
+            let Some((nb_vars, _, _)) = header_info else {
+                let (_, span) = line_words.next().unwrap();
+                return Err(DimacsError::MissingHeader(span));
+            };
+            for (word, span) in line_words {
+                let literal = word
+                    .parse::<i64>()
+                    .map_err(|_| DimacsError::InvalidLiteral(word.to_string(), span))?;
+                if literal == 0 {
+                    clauses.push(Clause::new(clause.drain(..)));
+                } else if literal.unsigned_abs() > nb_vars as u64 {
+                    return Err(DimacsError::LiteralOutOfRange(literal, nb_vars, span));
+                } else {
+                    clause.push(Literal::from_signed(literal as i32));
+                    last_literal = span;
+                }
+            }
+        }
+
+        let Some((nb_vars, nb_clauses, clauses_span)) = header_info else {
+            return Err(DimacsError::MissingHeader(Span::at(dimacs.len())));
+        };
+        if !clause.is_empty() {
+            return Err(DimacsError::UnterminatedClause(last_literal));
+        }
+        if clauses.len() != nb_clauses {
+            return Err(DimacsError::ClauseCount(
+                nb_clauses,
+                clauses.len(),
+                clauses_span,
+            ));
+        }
+
+        // The first name given to a variable, if no other variable has it
+        let mut variables = vec![None; nb_vars];
+        let mut used = HashSet::new();
+        for (number, name) in names {
+            if (1..=nb_vars).contains(&number)
+                && variables[number - 1].is_none()
+                && used.insert(name)
+            {
+                variables[number - 1] = Some(name);
+            }
+        }
+        let variables = variables
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| {
+                name.unwrap_or_else(|| {
+                    let mut name = format!("x{}", i + 1);
+                    while used.contains(&Var::new(&name)) {
+                        name.push('_');
+                    }
+                    Var::new(&name)
+                })
+            })
+            .collect();
+
+        let mut cnf = Cnf::new(variables);
+        for clause in clauses {
+            cnf.push(clause);
+        }
+        Ok(cnf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bool_formula_ast::Node;
+    use crate::ex06_conjunctive_normal_form::clauses;
+
+    #[test]
+    fn export() {
+        let cnf = clauses("[x1]B^C|").unwrap();
+        assert_eq!(
+            cnf.to_dimacs(),
+            "c 1 B\n\
+             c 2 C\n\
+             c 3 x1\n\
+             p cnf 3 2\n\
+             1 2 3 0\n\
+             -3 -1 2 0\n"
+        );
+        assert_eq!(Cnf::from_dimacs(&cnf.to_dimacs()).unwrap(), cnf);
+        assert_eq!(
+            Cnf::from_node(&Node::Value(true)).to_dimacs(),
+            "p cnf 0 0\n"
+        );
+    }
+
+    #[test]
+    fn import() {
+        let cnf = Cnf::from_dimacs(
+            "c uf3-01.cnf\n\
+             c 2 carry\n\
+             c 3 x1\n\
+             p  cnf 3  3\n\
+             \x20 1 -2\n\
+             0 -1 3 0\n\
+             \n\
+             0\n\
+             %\n\
+             0\n",
+        )
+        .unwrap();
+        assert_eq!(
+            cnf.variables(),
+            [Var::new("x1_"), Var::new("carry"), Var::new("x1")]
+        );
+        assert_eq!(cnf.to_string(), "(-2 1) (-1 3) ()");
+        assert_eq!(cnf.to_node().to_string(), "[carry]![x1_]|[x1_]![x1]|0&&");
+    }
+
+    #[test]
+    fn errors() {
+        fn error(dimacs: &str) -> (String, &str) {
+            let error = Cnf::from_dimacs(dimacs).unwrap_err();
+            let span = error.span().unwrap();
+            (error.to_string(), &dimacs[span.start..span.end])
+        }
+        assert_eq!(
+            error("1 2 0\n"),
+            ("missing `p cnf` header".to_string(), "1")
+        );
+        assert_eq!(error("c 1 A\n").0, "missing `p cnf` header");
+        assert_eq!(
+            error("p sat 2 1\n"),
+            (
+                "invalid header: expected `cnf`, found `sat`".to_string(),
+                "sat"
+            )
+        );
+        assert_eq!(
+            error("p cnf -2 1\n"),
+            (
+                "invalid header: `-2` is not a number of variables".to_string(),
+                "-2"
+            )
+        );
+        assert_eq!(
+            error("p cnf 2\n").0,
+            "invalid header: missing the number of clauses"
+        );
+        assert_eq!(
+            error("p cnf 2 1 3\n"),
+            ("invalid header: unexpected `3`".to_string(), "3")
+        );
+        assert_eq!(
+            error("p cnf 2 1\np cnf 2 1\n"),
+            ("invalid header: duplicate header".to_string(), "p")
+        );
+        assert_eq!(
+            error("p cnf 2 1\n1 -3 0\n"),
+            (
+                "literal -3 is out of range, there are 2 variables".to_string(),
+                "-3"
+            )
+        );
+        assert_eq!(
+            error("p cnf 2 1\n1 B 0\n"),
+            ("unexpected 'B'".to_string(), "B")
+        );
+        assert_eq!(
+            error("p cnf 2 1\n1 -2\n"),
+            ("clause is not terminated by `0`".to_string(), "-2")
+        );
+        assert_eq!(
+            error("p cnf 2 2\n1 -2 0\n"),
+            ("the header announces 2 clauses, found 1".to_string(), "2")
+        );
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..100 {
+            let mut tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            tree.to_primitive_connectives_mut();
+            tree.to_negation_normal_form_mut();
+            let formula = tree.to_string();
+            if formula.len() > 100 {
+                // That's a tad big
+                continue;
+            }
+            let cnf = clauses(&formula).unwrap();
+            assert_eq!(
+                Cnf::from_dimacs(&cnf.to_dimacs()).unwrap(),
+                cnf,
+                "{formula}"
+            );
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! <https://en.wikipedia.org/wiki/Disjunctive_normal_form>
+use crate::bool_formula_ast::{MyError, Node, Oper};
+
+impl Node {
+    pub fn is_disjunctive_normal_form(&mut self, accept_disjunctions: bool) -> bool {
+        self.is_normal_form(Oper::Disjunction, Oper::Conjunction, accept_disjunctions)
+    }
+
+    /// `self` MUST be in negation normal form
+    ///
+    /// `&` is distributed over `|`, the result is a single n-ary `|` of terms,
+    /// each a single n-ary `&` of literals.
+    pub fn to_disjunctive_normal_form_mut(&mut self) {
+        self.distribute_mut(Oper::Disjunction, Oper::Conjunction);
+    }
+}
+
+fn dnf(formula: &str) -> Result<Node, MyError> {
+    let mut tree = Node::parse(formula)?;
+    tree.to_primitive_connectives_mut();
+    tree.to_negation_normal_form_mut();
+    tree.to_disjunctive_normal_form_mut();
+    Ok(tree)
+}
+
+pub fn disjunctive_normal_form(formula: &str) -> String {
+    dnf(formula)
+        .map(|n| n.to_string())
+        .unwrap_or_else(|e| e.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bool_formula_ast::Op;
+    use crate::ex04_truth_table::TruthTable;
+
+    #[test]
+    fn examples() {
+        assert_eq!(disjunctive_normal_form("AB&!"), "A!B!|");
+        assert_eq!(disjunctive_normal_form("AB|!"), "A!B!&");
+        assert_eq!(disjunctive_normal_form("AB|C&"), "AC&BC&|");
+        assert_eq!(disjunctive_normal_form("AB|C|D|"), "ABCD|||");
+        assert_eq!(disjunctive_normal_form("AB&C&D&"), "ABCD&&&");
+        assert_eq!(disjunctive_normal_form("AB>"), "A!B|");
+        assert_eq!(disjunctive_normal_form("AB|!C!&"), "A!B!C!&&");
+        // Contradictory terms are kept, see `Node::simplify`
+        assert_eq!(disjunctive_normal_form("AB^"), "AA!&AB!&BA!&BB!&|||");
+    }
+
+    #[test]
+    fn named_variables() {
+        assert_eq!(
+            disjunctive_normal_form("[x1][x2]|[y]&"),
+            "[x1][y]&[x2][y]&|"
+        );
+    }
+
+    #[test]
+    fn n_ary() {
+        let mut tree = Node::parse("AB|C|DE|&").unwrap();
+        tree.flatten_mut();
+        tree.to_disjunctive_normal_form_mut();
+        assert_eq!(tree.to_string(), "AD&AE&BD&BE&CD&CE&|||||");
+        let Node::Operator(Op { children, .. }) = &tree else {
+            panic!("{tree:?}");
+        };
+        assert_eq!(children.len(), 6);
+        assert!(tree.is_disjunctive_normal_form(true));
+        assert!(
+            !Node::parse("AB|C&")
+                .unwrap()
+                .is_disjunctive_normal_form(true)
+        );
+        assert!(
+            !Node::parse("AB|C|D&")
+                .unwrap()
+                .is_disjunctive_normal_form(true)
+        );
+        assert!(
+            !Node::parse("AB|!")
+                .unwrap()
+                .is_disjunctive_normal_form(true)
+        );
+        assert!(
+            !Node::parse("AB&!C|")
+                .unwrap()
+                .is_disjunctive_normal_form(true)
+        );
+        assert!(
+            Node::parse("A!1!&C|")
+                .unwrap()
+                .is_disjunctive_normal_form(true)
+        );
+    }
+
+    #[test]
+    fn huge_formula() {
+        let formula = crate::bool_formula_ast::huge_chain('&');
+        let mut tree = Node::parse(formula.as_str()).unwrap();
+        tree.to_disjunctive_normal_form_mut();
+        assert_eq!(tree.children().len(), 5_000_001);
+        assert!(tree.to_string().ends_with(&"&".repeat(5_000_000)));
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..100 {
+            let mut tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            tree.to_primitive_connectives_mut();
+            tree.to_negation_normal_form_mut();
+            let formula = tree.to_string();
+            if formula.len() > 100 {
+                // That's a tad big
+                continue;
+            }
+
+            let res = disjunctive_normal_form(&formula);
+            let mut res_tree = Node::parse(&res).unwrap();
+            println!("{formula} -> {res}");
+            assert!(res_tree.is_disjunctive_normal_form(true));
+            let orig_truth_table = TruthTable::compute(&formula).unwrap();
+            let dnf_truth_table =
+                TruthTable::compute_with_given_vars(&res, orig_truth_table.variables().to_vec())
+                    .unwrap();
+            assert_eq!(orig_truth_table.to_string(), dnf_truth_table.to_string());
+        }
+    }
+}
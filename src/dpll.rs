@@ -0,0 +1,269 @@
+//! <https://en.wikipedia.org/wiki/DPLL_algorithm> on a [`Cnf`], see [`dpll`].
+//!
+//! Every clause counts its true and unassigned literals, every literal the unsatisfied clauses
+//! it appears in, so unit clauses, conflicts and pure literals are found without scanning the formula.
+use crate::clause::{Cnf, Literal};
+
+struct Solver<'a> {
+    cnf: &'a Cnf,
+    /// Clauses of each literal
+    occurrences: Vec<Vec<usize>>,
+    values: Vec<Option<bool>>,
+    /// True literals of each clause
+    true_literals: Vec<u32>,
+    /// Unassigned literals of each clause
+    unassigned: Vec<u32>,
+    /// Unsatisfied clauses of each literal
+    active: Vec<u32>,
+    unsatisfied: usize,
+    /// Assigned literals, in order
+    trail: Vec<Literal>,
+    /// Clauses that may have become unit
+    units: Vec<usize>,
+    conflict: bool,
+}
+
+impl<'a> Solver<'a> {
+    fn new(cnf: &'a Cnf) -> Self {
+        let nb_vars = cnf.variables().len();
+        let mut occurrences = vec![Vec::new(); 2 * nb_vars];
+        let mut active = vec![0; 2 * nb_vars];
+        for (i, clause) in cnf.clauses().iter().enumerate() {
+            for &literal in clause.literals() {
+                occurrences[literal.index()].push(i);
+                active[literal.index()] += 1;
+            }
+        }
+        let unassigned = cnf
+            .clauses()
+            .iter()
+            .map(|clause| clause.len() as u32)
+            .collect::<Vec<_>>();
+        Self {
+            cnf,
+            occurrences,
+            values: vec![None; nb_vars],
+            true_literals: vec![0; unassigned.len()],
+            units: (0..unassigned.len())
+                .filter(|&i| unassigned[i] <= 1)
+                .collect(),
+            conflict: unassigned.contains(&0),
+            unassigned,
+            active,
+            unsatisfied: cnf.clauses().len(),
+            trail: Vec::new(),
+        }
+    }
+
+    fn value(&self, literal: Literal) -> Option<bool> {
+        self.values[literal.var()].map(|value| value == literal.is_positive())
+    }
+
+    fn assign(&mut self, literal: Literal) {
+        self.values[literal.var()] = Some(literal.is_positive());
+        self.trail.push(literal);
+        for &clause in &self.occurrences[literal.index()] {
+            self.true_literals[clause] += 1;
+            self.unassigned[clause] -= 1;
+            if self.true_literals[clause] == 1 {
+                self.unsatisfied -= 1;
+                for &other in self.cnf.clauses()[clause].literals() {
+                    self.active[other.index()] -= 1;
+                }
+            }
+        }
+        for &clause in &self.occurrences[(!literal).index()] {
+            self.unassigned[clause] -= 1;
+            if self.true_literals[clause] == 0 {
+                match self.unassigned[clause] {
+                    0 => self.conflict = true,
+                    1 => self.units.push(clause),
+                    _ => (),
+                }
+            }
+        }
+    }
+
+    /// Unassigns the literals assigned after the first `len`
+    fn backtrack(&mut self, len: usize) {
+        while self.trail.len() > len {
+            let literal = self.trail.pop().unwrap();
+            self.values[literal.var()] = None;
+            for &clause in &self.occurrences[literal.index()] {
+                self.true_literals[clause] -= 1;
+                self.unassigned[clause] += 1;
+                if self.true_literals[clause] == 0 {
+                    self.unsatisfied += 1;
+                    for &other in self.cnf.clauses()[clause].literals() {
+                        self.active[other.index()] += 1;
+                    }
+                }
+            }
+            for &clause in &self.occurrences[(!literal).index()] {
+                self.unassigned[clause] += 1;
+            }
+        }
+        self.units.clear();
+        self.conflict = false;
+    }
+
+    /// Assigns the last literal of unit clauses until there are none, false on a conflict
+    fn propagate(&mut self) -> bool {
+        while let Some(clause) = self.units.pop() {
+            if self.conflict {
+                return false;
+            }
+            if self.true_literals[clause] > 0 || self.unassigned[clause] != 1 {
+                continue;
+            }
+            let literal = *self.cnf.clauses()[clause]
+                .literals()
+                .iter()
+                .find(|&&literal| self.value(literal).is_none())
+                .unwrap();
+            self.assign(literal);
+        }
+        !self.conflict
+    }
+
+    /// A literal of an unassigned variable whose negation is in no unsatisfied clause
+    fn pure_literal(&self) -> Option<Literal> {
+        (0..self.values.len())
+            .filter(|&var| self.values[var].is_none())
+            .find_map(|var| {
+                let (positive, negative) = (Literal::new(var, true), Literal::new(var, false));
+                match (self.active[positive.index()], self.active[negative.index()]) {
+                    (0, 0) => None,
+                    (_, 0) => Some(positive),
+                    (0, _) => Some(negative),
+                    _ => None,
+                }
+            })
+    }
+
+    /// The literal of an unassigned variable in the most unsatisfied clauses
+    fn branch(&self) -> Option<Literal> {
+        (0..self.values.len())
+            .filter(|&var| self.values[var].is_none())
+            .flat_map(|var| [Literal::new(var, true), Literal::new(var, false)])
+            .filter(|&literal| self.active[literal.index()] > 0)
+            .max_by_key(|&literal| self.active[literal.index()])
+    }
+
+    fn solve(mut self) -> Option<Vec<bool>> {
+        // Trail length before each decision, the decision, and whether its negation was tried
+        let mut decisions: Vec<(usize, Literal, bool)> = Vec::new();
+        loop {
+            if !self.propagate() {
+                loop {
+                    let (len, decision, flipped) = decisions.pop()?;
+                    self.backtrack(len);
+                    if !flipped {
+                        decisions.push((len, !decision, true));
+                        self.assign(!decision);
+                        break;
+                    }
+                }
+                continue;
+            }
+            if self.unsatisfied == 0 {
+                break;
+            }
+            if let Some(literal) = self.pure_literal() {
+                self.assign(literal);
+                continue;
+            }
+            let decision = self
+                .branch()
+                .expect("an unsatisfied clause has no unassigned literal");
+            decisions.push((self.trail.len(), decision, false));
+            self.assign(decision);
+        }
+        Some(
+            self.values
+                .into_iter()
+                .map(|value| value.unwrap_or(false))
+                .collect(),
+        )
+    }
+}
+
+/// A model of `cnf` indexed like its variables, `None` if it is unsatisfiable.
+///
+/// Unit propagation and pure literal elimination after every decision,
+/// branches on the literal in the most unsatisfied clauses.
+pub fn dpll(cnf: &Cnf) -> Option<Vec<bool>> {
+    Solver::new(cnf).solve()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_parallel::satisfiable;
+    use crate::clause::generators::cnf;
+    use rand::Rng;
+
+    #[test]
+    fn examples() {
+        assert_eq!(dpll(&cnf(0, &[])), Some(vec![]));
+        assert_eq!(dpll(&cnf(1, &[&[]])), None);
+        assert_eq!(dpll(&cnf(2, &[&[1, 2], &[-1], &[-2]])), None);
+        assert_eq!(
+            dpll(&cnf(3, &[&[1, 2], &[-1, 3], &[-3]])),
+            Some(vec![false, true, false])
+        );
+        // Pure: `x3` is only positive
+        let model = dpll(&cnf(3, &[&[1, 3], &[-1, 3], &[1, -2], &[-1, 2]])).unwrap();
+        assert!(model[2]);
+
+        // 3 pigeons, 2 holes: `xi` is pigeon `i / 2` in hole `i % 2`
+        let pigeons = [&[1, 2][..], &[3, 4], &[5, 6]];
+        let holes = [[-1, -3], [-1, -5], [-3, -5], [-2, -4], [-2, -6], [-4, -6]];
+        let mut clauses = pigeons.to_vec();
+        clauses.extend(holes.iter().map(|hole| &hole[..]));
+        assert_eq!(dpll(&cnf(6, &clauses)), None);
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..200 {
+            let nb_vars = rng.gen_range(1..=8);
+            let nb_clauses = rng.gen_range(0..=5 * nb_vars);
+            let clauses = (0..nb_clauses)
+                .map(|_| {
+                    (0..rng.gen_range(1..=3))
+                        .map(|_| {
+                            let var = rng.gen_range(1..=nb_vars as i32);
+                            if rng.r#gen() { var } else { -var }
+                        })
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            let clauses = clauses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+            let cnf = cnf(nb_vars, &clauses);
+
+            let model = dpll(&cnf);
+            assert_eq!(model.is_some(), satisfiable(&cnf.to_node()), "{cnf}");
+            if let Some(model) = model {
+                assert!(cnf.evaluate(&model), "{cnf}");
+            }
+        }
+    }
+
+    #[test]
+    fn many_variables() {
+        // x1 > x2 > ... > x10000, x1 and !x10000
+        let n = 10_000;
+        let mut clauses = (1..n).map(|i| vec![-i, i + 1]).collect::<Vec<_>>();
+        clauses.push(vec![1]);
+        let satisfied = clauses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        assert_eq!(
+            dpll(&cnf(n as usize, &satisfied)),
+            Some(vec![true; n as usize])
+        );
+        clauses.push(vec![-n]);
+        let contradiction = clauses.iter().map(Vec::as_slice).collect::<Vec<_>>();
+        assert_eq!(dpll(&cnf(n as usize, &contradiction)), None);
+    }
+}
@@ -0,0 +1,478 @@
+//! Heuristic two-level minimization of multi-output functions, see [`espresso`].
+//!
+//! A simplified <https://en.wikipedia.org/wiki/Espresso_heuristic_logic_minimizer>:
+//! the cover is expanded to prime implicants, stripped of the redundant ones, then reduced
+//! to let the next expansion go another way, for as long as it gets smaller.
+//! Unlike [`TruthTable::quine_mccluskey`] it never lists every prime implicant or row,
+//! the functions are lists of [`Cube`]s.
+use thiserror::Error;
+
+use crate::bool_formula_ast::Node;
+use crate::cube::{Cube, complement, sum_of_products};
+use crate::ex04_truth_table::{TruthTable, TruthTableError};
+use crate::variable::Var;
+
+#[derive(Error, Debug)]
+pub enum EspressoError {
+    #[error("too many variables: {0}, cubes have at most 64")]
+    TooManyVariables(usize),
+    #[error("too many outputs: {0}, at most 64")]
+    TooManyOutputs(usize),
+    #[error("{0} don't care covers for {1} outputs")]
+    DontCareCount(usize, usize),
+    #[error("a cube of output {0} has a literal past the {1} variables")]
+    CubeOutOfRange(usize, usize),
+    #[error(transparent)]
+    TruthTable(#[from] TruthTableError),
+}
+
+/// Cube shared by some outputs of a multi-output cover.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Implicant {
+    pub cube: Cube,
+    /// Bit `i` is set when the cube is part of output `i`
+    pub outputs: u64,
+}
+
+impl Implicant {
+    fn outputs(&self) -> impl Iterator<Item = usize> + use<> {
+        let outputs = self.outputs;
+        (0..64).filter(move |o| outputs >> o & 1 == 1)
+    }
+
+    fn covers(&self, other: &Implicant) -> bool {
+        self.cube.covers(&other.cube) && other.outputs & !self.outputs == 0
+    }
+}
+
+/// Sums of products of the outputs found by [`espresso`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cover {
+    variables: Vec<Var>,
+    nb_outputs: usize,
+    implicants: Vec<Implicant>,
+}
+
+impl Cover {
+    pub fn variables(&self) -> &[Var] {
+        &self.variables
+    }
+
+    pub fn nb_outputs(&self) -> usize {
+        self.nb_outputs
+    }
+
+    pub fn implicants(&self) -> &[Implicant] {
+        &self.implicants
+    }
+
+    /// Cubes of `output`
+    pub fn cubes(&self, output: usize) -> Vec<Cube> {
+        self.implicants
+            .iter()
+            .filter(|implicant| implicant.outputs >> output & 1 == 1)
+            .map(|implicant| implicant.cube)
+            .collect()
+    }
+
+    /// Number of input literals, a cube shared by outputs counts once
+    pub fn literals(&self) -> u32 {
+        self.implicants.iter().map(|i| i.cube.literals()).sum()
+    }
+
+    pub fn to_node(&self, output: usize) -> Node {
+        sum_of_products(&self.cubes(output), &self.variables)
+    }
+
+    pub fn to_truth_table(&self, output: usize) -> Result<TruthTable, TruthTableError> {
+        let cubes = self.cubes(output);
+        TruthTable::from_fn(self.variables.clone(), |row| {
+            cubes.iter().any(|cube| cube.contains(row))
+        })
+    }
+}
+
+/// Fewer cubes first, then fewer literals
+fn cost(cover: &[Implicant]) -> (usize, u32) {
+    let literals = cover.iter().map(|i| i.cube.literals()).sum();
+    (cover.len(), literals)
+}
+
+/// Whether some cube of `cubes` intersects `cube`
+fn intersects(cubes: &[Cube], cube: &Cube) -> bool {
+    cubes.iter().any(|other| other.intersection(cube).is_some())
+}
+
+/// Disjoint pieces of `cube` outside of `others`
+fn uncovered<'a>(cube: &Cube, others: impl Iterator<Item = &'a Cube>) -> Vec<Cube> {
+    let mut pieces = vec![*cube];
+    for other in others {
+        pieces = pieces
+            .iter()
+            .flat_map(|piece| piece.difference(other))
+            .collect();
+        if pieces.is_empty() {
+            break;
+        }
+    }
+    pieces
+}
+
+/// The function being minimized.
+struct Problem {
+    nb_vars: usize,
+    /// Per output, the cubes whose rows must be `1`
+    required: Vec<Vec<Cube>>,
+    /// Per output, the cubes whose rows must be `0`
+    off: Vec<Vec<Cube>>,
+}
+
+impl Problem {
+    /// The cube holds no row that must be `0` in any of its outputs
+    fn is_valid(&self, implicant: &Implicant) -> bool {
+        implicant
+            .outputs()
+            .all(|o| !intersects(&self.off[o], &implicant.cube))
+    }
+
+    /// Pieces of the `i`th cube of `cover` that no other cube of `cover` has in output `o`
+    fn uncovered(&self, cover: &[Implicant], i: usize, o: usize) -> Vec<Cube> {
+        let others = cover[..i].iter().chain(&cover[i + 1..]);
+        let others = others.filter(|other| other.outputs >> o & 1 == 1);
+        uncovered(&cover[i].cube, others.map(|other| &other.cube))
+    }
+
+    /// Grows every cube into a prime implicant, dropping the cubes that end up inside another.
+    fn expand(&self, cover: &mut Vec<Implicant>) {
+        // Biggest cubes first, they are the most likely to swallow others
+        cover.sort_by_key(|implicant| implicant.cube.literals());
+        let mut expanded: Vec<Implicant> = Vec::with_capacity(cover.len());
+        for mut implicant in cover.drain(..) {
+            if expanded.iter().any(|e| e.covers(&implicant)) {
+                continue;
+            }
+            for bit in (0..self.nb_vars as u32).rev() {
+                if implicant.cube.mask() >> bit & 1 == 0 {
+                    continue;
+                }
+                let raised = Implicant {
+                    cube: implicant.cube.without(bit),
+                    ..implicant
+                };
+                if self.is_valid(&raised) {
+                    implicant = raised;
+                }
+            }
+            // Share the cube with the other outputs it fits in
+            for o in 0..self.required.len() {
+                if !intersects(&self.off[o], &implicant.cube)
+                    && intersects(&self.required[o], &implicant.cube)
+                {
+                    implicant.outputs |= 1 << o;
+                }
+            }
+            expanded.retain(|e| !implicant.covers(e));
+            expanded.push(implicant);
+        }
+        *cover = expanded;
+    }
+
+    /// Removes cubes whose required rows are all covered by other cubes, smallest cubes first.
+    fn irredundant(&self, cover: &mut Vec<Implicant>) {
+        cover.sort_by_key(|implicant| std::cmp::Reverse(implicant.cube.literals()));
+        let mut i = 0;
+        while i < cover.len() {
+            let redundant = cover[i].outputs().all(|o| {
+                self.uncovered(cover, i, o)
+                    .iter()
+                    .all(|piece| !intersects(&self.required[o], piece))
+            });
+            if redundant {
+                cover.remove(i);
+            } else {
+                i += 1;
+            }
+        }
+    }
+
+    /// Shrinks every cube to the smallest one holding the rows no other cube covers.
+    fn reduce(&self, cover: &mut Vec<Implicant>) {
+        cover.sort_by_key(|implicant| implicant.cube.literals());
+        let mut i = 0;
+        while i < cover.len() {
+            let (mut reduced, mut outputs) = (None, 0);
+            for o in cover[i].outputs() {
+                for piece in self.uncovered(cover, i, o) {
+                    // The required rows of the piece
+                    for rows in self.required[o]
+                        .iter()
+                        .filter_map(|r| r.intersection(&piece))
+                    {
+                        reduced = Some(reduced.map_or(rows, |cube: Cube| cube.supercube(&rows)));
+                        outputs |= 1 << o;
+                    }
+                }
+            }
+            match reduced {
+                Some(cube) => {
+                    cover[i] = Implicant { cube, outputs };
+                    i += 1;
+                }
+                None => {
+                    cover.remove(i);
+                }
+            }
+        }
+    }
+}
+
+/// Heuristic minimal sums of products over `variables`: output `i` must be `1` on the cubes
+/// of `on[i]`, except on those of `dont_care[i]`, and `0` elsewhere.
+///
+/// Cubes have the bit layout of [`Cube`], the first variable is the most significant bit.
+/// `dont_care` may be shorter than `on`.
+pub fn espresso(
+    variables: Vec<Var>,
+    on: &[Vec<Cube>],
+    dont_care: &[Vec<Cube>],
+) -> Result<Cover, EspressoError> {
+    let nb_vars = variables.len();
+    if nb_vars > 64 {
+        return Err(EspressoError::TooManyVariables(nb_vars));
+    }
+    if on.len() > 64 {
+        return Err(EspressoError::TooManyOutputs(on.len()));
+    }
+    if dont_care.len() > on.len() {
+        return Err(EspressoError::DontCareCount(dont_care.len(), on.len()));
+    }
+    let outside = u64::MAX.checked_shl(nb_vars as u32).unwrap_or(0);
+    for (o, cubes) in on.iter().chain(dont_care).enumerate() {
+        if cubes.iter().any(|cube| cube.mask() & outside != 0) {
+            return Err(EspressoError::CubeOutOfRange(o % on.len(), nb_vars));
+        }
+    }
+
+    let (mut required, mut off) = (Vec::new(), Vec::new());
+    for (o, on) in on.iter().enumerate() {
+        let dont_care = dont_care.get(o).map_or(&[][..], Vec::as_slice);
+        required.push(
+            on.iter()
+                .flat_map(|cube| uncovered(cube, dont_care.iter()))
+                .collect::<Vec<_>>(),
+        );
+        off.push(complement(&[&on[..], dont_care].concat()));
+    }
+    let problem = Problem {
+        nb_vars,
+        required,
+        off,
+    };
+
+    // The cubes of every output on its own, `expand` shares them
+    let mut cover = (0..on.len())
+        .flat_map(|o| {
+            problem.required[o].iter().map(move |&cube| Implicant {
+                cube,
+                outputs: 1 << o,
+            })
+        })
+        .collect::<Vec<_>>();
+    problem.expand(&mut cover);
+    problem.irredundant(&mut cover);
+    let mut best = cover.clone();
+    loop {
+        problem.reduce(&mut cover);
+        problem.expand(&mut cover);
+        problem.irredundant(&mut cover);
+        if cost(&cover) >= cost(&best) {
+            break;
+        }
+        best = cover.clone();
+    }
+
+    best.sort();
+    Ok(Cover {
+        variables,
+        nb_outputs: on.len(),
+        implicants: best,
+    })
+}
+
+impl TruthTable {
+    /// Single output [`espresso`], the rows in `dont_cares` may have any result.
+    pub fn espresso(&self, dont_cares: &[u64]) -> Result<Cover, EspressoError> {
+        let nb_vars = self.variables().len();
+        if let Some(&row) = dont_cares.iter().find(|&&row| row >= self.rows()) {
+            return Err(TruthTableError::RowOutOfRange(row, self.rows()).into());
+        }
+        let dont_care = dont_cares
+            .iter()
+            .map(|&row| Cube::minterm(row, nb_vars))
+            .collect();
+        espresso(self.variables().to_vec(), &[self.cubes()], &[dont_care])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The cover matches the function on the rows that matter and can't be made smaller
+    /// by removing a cube or a literal.
+    fn assert_valid(cover: &Cover, on: &[TruthTable], dont_care: &[TruthTable]) {
+        let variables = cover.variables().to_vec();
+        let (mut allowed, mut required) = (Vec::new(), Vec::new());
+        for (o, on) in on.iter().enumerate() {
+            let on = on.with_variables(variables.clone()).unwrap();
+            let dont_care = dont_care[o].with_variables(variables.clone()).unwrap();
+            let result = cover.to_truth_table(o).unwrap();
+            assert_eq!(&result & &!&dont_care, &on & &!&dont_care, "output {o}");
+            allowed.push(&on | &dont_care);
+            required.push(&on & &!&dont_care);
+        }
+        let implicants = cover.implicants();
+        for (i, implicant) in implicants.iter().enumerate() {
+            let Implicant { cube, outputs } = *implicant;
+            let fits = |cube: &Cube| implicant.outputs().all(|o| allowed[o].covers_cube(cube));
+            assert!(fits(&cube));
+            for bit in (0..variables.len() as u32).filter(|bit| cube.mask() >> bit & 1 == 1) {
+                assert!(!fits(&cube.without(bit)), "{implicant:?} isn't prime");
+            }
+            // Only this cube covers one of the required rows
+            let needed = implicant.outputs().any(|o| {
+                cube.rows(variables.len()).any(|row| {
+                    required[o].get(row)
+                        && !implicants.iter().enumerate().any(|(j, other)| {
+                            j != i && other.outputs >> o & 1 == 1 && other.cube.contains(row)
+                        })
+                })
+            });
+            assert!(needed, "{implicant:?} is redundant");
+            assert_ne!(outputs, 0);
+        }
+    }
+
+    #[test]
+    fn single_output() {
+        let vars = ['A', 'B', 'C', 'D'].map(Var::from).to_vec();
+        let table = TruthTable::from_minterms(vars.clone(), &[4, 8, 10, 11, 12, 15]).unwrap();
+        let cover = table.espresso(&[9, 14]).unwrap();
+        let dont_care = TruthTable::from_minterms(vars, &[9, 14]).unwrap();
+        assert_valid(&cover, std::slice::from_ref(&table), &[dont_care]);
+        assert_eq!(cost(cover.implicants()), (3, 7));
+        assert_eq!(
+            cover.literals(),
+            table.quine_mccluskey(&[9, 14]).unwrap().literals()
+        );
+
+        let never = TruthTable::compute("A!A&").unwrap();
+        assert_eq!(never.espresso(&[]).unwrap().to_node(0), Node::Value(false));
+        let always = TruthTable::compute("AA!|").unwrap();
+        assert_eq!(always.espresso(&[]).unwrap().to_node(0), Node::Value(true));
+        assert!(matches!(
+            always.espresso(&[2]),
+            Err(EspressoError::TruthTable(TruthTableError::RowOutOfRange(
+                2, 2
+            )))
+        ));
+    }
+
+    #[test]
+    fn errors() {
+        let vars = |n| {
+            (0..n)
+                .map(|i| Var::new(&format!("x{i}")))
+                .collect::<Vec<_>>()
+        };
+        assert!(matches!(
+            espresso(vars(65), &[], &[]),
+            Err(EspressoError::TooManyVariables(65))
+        ));
+        assert!(matches!(
+            espresso(vars(2), &vec![vec![]; 65], &[]),
+            Err(EspressoError::TooManyOutputs(65))
+        ));
+        assert!(matches!(
+            espresso(vars(2), &[vec![]], &[vec![], vec![]]),
+            Err(EspressoError::DontCareCount(2, 1))
+        ));
+        assert!(matches!(
+            espresso(vars(2), &[vec![], vec![Cube::minterm(4, 3)]], &[]),
+            Err(EspressoError::CubeOutOfRange(1, 2))
+        ));
+    }
+
+    #[test]
+    fn beyond_truth_tables() {
+        // x0 & x1 | x0 & x1 & x2 | x2 & x3 over 40 variables, too many rows for a `TruthTable`
+        let variables = (0..40)
+            .map(|i| Var::new(&format!("x{i}")))
+            .collect::<Vec<_>>();
+        let literal = |i: u32| 1 << (39 - i);
+        let x0x1 = Cube::new(literal(0) | literal(1), literal(0) | literal(1));
+        let x0x1x2 = Cube::new(x0x1.bits() | literal(2), x0x1.mask() | literal(2));
+        let x2x3 = Cube::new(literal(2) | literal(3), literal(2) | literal(3));
+        let cover = espresso(variables, &[vec![x0x1, x0x1x2, x2x3]], &[]).unwrap();
+        assert_eq!(cost(cover.implicants()), (2, 4));
+        assert_eq!(cover.to_node(0).to_string(), "[x2][x3]&[x0][x1]&|");
+    }
+
+    #[test]
+    fn multi_output() {
+        let variables = ['A', 'B', 'C'].map(Var::from).to_vec();
+        let on = ["AB&C|", "AB&C!|", "AB&"].map(|formula| {
+            TruthTable::compute_with_given_vars(formula, variables.clone()).unwrap()
+        });
+        let cubes = on.iter().map(TruthTable::cubes).collect::<Vec<_>>();
+        let cover = espresso(variables, &cubes, &[]).unwrap();
+        let empty = TruthTable::compute("0").unwrap();
+        assert_valid(&cover, &on, &[empty.clone(), empty.clone(), empty]);
+        // `AB` is shared by the three outputs
+        assert_eq!(cover.implicants().len(), 3);
+        assert_eq!(cover.to_node(2).to_string(), "AB&");
+        assert_eq!(cover.to_node(0).to_string(), "CAB&|");
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        let vars = ['A', 'B', 'C', 'D', 'E', 'F'];
+        let variables = vars.map(Var::from).to_vec();
+        for _ in 0..20 {
+            let on = (0..3)
+                .map(|_| {
+                    let tree = Node::new_random(&vars);
+                    TruthTable::compute_with_given_vars(&tree.to_string(), variables.clone())
+                        .unwrap()
+                })
+                .collect::<Vec<_>>();
+            let dont_care = (0..3)
+                .map(|_| {
+                    TruthTable::from_fn(variables.clone(), |_| rand::random::<u8>() < 32).unwrap()
+                })
+                .collect::<Vec<_>>();
+            let cubes =
+                |tables: &[TruthTable]| tables.iter().map(TruthTable::cubes).collect::<Vec<_>>();
+            let cover = espresso(variables.clone(), &cubes(&on), &cubes(&dont_care)).unwrap();
+            assert_valid(&cover, &on, &dont_care);
+
+            let single = on[0].espresso(&[]).unwrap();
+            let exact = on[0].quine_mccluskey(&[]).unwrap();
+            assert_eq!(single.to_truth_table(0).unwrap(), on[0]);
+            assert!(single.implicants().len() >= exact.cover().len());
+        }
+    }
+
+    #[test]
+    fn many_variables() {
+        // `x0 & x1 | x2 & x3 | ... | x16 & x17`, 2^18 rows
+        let formula = (0..9)
+            .map(|i| format!("[x{}][x{}]&", 2 * i, 2 * i + 1))
+            .collect::<String>()
+            + &"|".repeat(8);
+        let table = TruthTable::compute(&formula).unwrap();
+        let cover = table.espresso(&[]).unwrap();
+        assert_eq!(cost(cover.implicants()), (9, 18));
+        assert_eq!(cover.to_truth_table(0).unwrap(), table);
+    }
+}
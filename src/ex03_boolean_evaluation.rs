@@ -1,62 +1,83 @@
+use std::collections::{BTreeMap, HashMap};
+
 use thiserror::Error;
 
+use crate::bool_formula_ast::{MyError, Node, Op};
+use crate::diagnostic::{Span, Spanned};
+use crate::variable::Var;
+
 #[derive(Error, Debug)]
 pub enum ParsingError {
     #[error("invalid character: '{0}'")]
-    InvalidChar(char),
+    InvalidChar(char, Span),
     #[error("missing value for operator: {0}")]
-    MissingValue(char),
+    MissingValue(char, Span),
     #[error("formula returns multiple values")]
-    TooManyValues,
+    TooManyValues(Span),
+    #[error("premature end of formula")]
+    Eof(Span),
+}
+
+impl Spanned for ParsingError {
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::InvalidChar(_, span)
+            | Self::MissingValue(_, span)
+            | Self::TooManyValues(span)
+            | Self::Eof(span) => Some(*span),
+        }
+    }
 }
 
-fn eval(a: bool, b: bool, op: u8) -> bool {
+fn eval(a: bool, b: bool, op: char) -> bool {
     match op {
         // negation ¬
-        b'!' => unreachable!("This special case should be handled elsewhere"),
+        '!' => unreachable!("This special case should be handled elsewhere"),
         // Conjunction ∧
-        b'&' => a && b,
+        '&' => a && b,
         // disjunction ∨
-        b'|' => a || b,
+        '|' => a || b,
         // exclusive disjunction ⊕
-        b'^' => a ^ b,
+        '^' => a ^ b,
         // material condition ⇒
-        b'>' => !a || b,
+        '>' => !a || b,
         // logical equivalence ⇔
-        b'=' => a == b,
+        '=' => a == b,
         _ => unreachable!("invalid operator"),
     }
 }
 
 pub fn checked_eval_formula(formula: &str) -> Result<bool, ParsingError> {
-    let mut val_stack = Vec::new();
+    // Values along with the offset at which the sub-formula that produced them starts
+    let mut val_stack: Vec<(bool, usize)> = Vec::new();
 
-    for &val in formula.as_bytes() {
+    for (i, val) in formula.char_indices() {
+        let span = Span::of_char(i, val);
         match val {
-            b'0' | b'1' => val_stack.push((val - b'0') != 0),
-            b'!' => {
+            '0' | '1' => val_stack.push((val == '1', i)),
+            '!' => {
                 // `!` is the only one that operates on a single value
-                let a = val_stack
+                let (a, _) = val_stack
                     .last_mut()
-                    .ok_or(ParsingError::MissingValue('!'))?;
+                    .ok_or(ParsingError::MissingValue('!', span))?;
                 *a ^= true;
             }
-            b'&' | b'|' | b'^' | b'>' | b'=' => {
-                let b = val_stack
+            '&' | '|' | '^' | '>' | '=' => {
+                let (b, _) = val_stack
                     .pop()
-                    .ok_or(ParsingError::MissingValue(val as char))?;
-                let a = val_stack
+                    .ok_or(ParsingError::MissingValue(val, span))?;
+                let (a, start) = val_stack
                     .pop()
-                    .ok_or(ParsingError::MissingValue(val as char))?;
-                val_stack.push(eval(a, b, val));
+                    .ok_or(ParsingError::MissingValue(val, span))?;
+                val_stack.push((eval(a, b, val), start));
             }
-            _ => return Err(ParsingError::InvalidChar(val as char)),
+            _ => return Err(ParsingError::InvalidChar(val, span)),
         }
     }
-    if val_stack.len() == 1 {
-        Ok(val_stack.pop().unwrap())
-    } else {
-        Err(ParsingError::TooManyValues)
+    match val_stack[..] {
+        [(val, _)] => Ok(val),
+        [] => Err(ParsingError::Eof(Span::at(0))),
+        [.., (_, root_start)] => Err(ParsingError::TooManyValues(Span::new(0, root_start))),
     }
 }
 
@@ -64,6 +85,86 @@ pub fn eval_formula(formula: &str) -> bool {
     checked_eval_formula(formula).unwrap()
 }
 
+/// Values given to the variables of a formula.
+pub trait Assignment {
+    /// `None` if `var` has no value.
+    fn value(&self, var: Var) -> Option<bool>;
+}
+
+impl Assignment for HashMap<Var, bool> {
+    fn value(&self, var: Var) -> Option<bool> {
+        self.get(&var).copied()
+    }
+}
+
+impl Assignment for BTreeMap<Var, bool> {
+    fn value(&self, var: Var) -> Option<bool> {
+        self.get(&var).copied()
+    }
+}
+
+impl<F: Fn(Var) -> Option<bool>> Assignment for F {
+    fn value(&self, var: Var) -> Option<bool> {
+        self(var)
+    }
+}
+
+/// Packs the values of up to 64 variables in an integer.
+///
+/// `variables[0]` is the most significant bit, so `bits` is also the index
+/// of the matching row in a [`TruthTable`](crate::ex04_truth_table::TruthTable).
+#[derive(Debug, Clone, Copy)]
+pub struct Bitmask<'a> {
+    variables: &'a [Var],
+    bits: u64,
+}
+
+impl<'a> Bitmask<'a> {
+    pub fn new(variables: &'a [Var], bits: u64) -> Self {
+        assert!(variables.len() <= 64, "more than 64 variables");
+        Self { variables, bits }
+    }
+}
+
+impl Assignment for Bitmask<'_> {
+    fn value(&self, var: Var) -> Option<bool> {
+        let i = self.variables.iter().position(|&v| v == var)?;
+        Some(self.bits >> (self.variables.len() - 1 - i) & 1 == 1)
+    }
+}
+
+impl Node {
+    /// Value of the formula when its variables take the values of `assignment`.
+    ///
+    /// Every variable must be assigned, even those that don't change the result (`A0&`).
+    pub fn evaluate<A: Assignment + ?Sized>(&self, assignment: &A) -> Result<bool, MyError> {
+        let mut values: Vec<bool> = Vec::new();
+        for node in self.post_order() {
+            let value = match node {
+                Node::Value(val) => *val,
+                Node::Variable(var) => {
+                    assignment.value(*var).ok_or(MyError::UnsetVariable(*var))?
+                }
+                Node::Neg(_) => !values.pop().unwrap(),
+                Node::Operator(Op { char, children }) => {
+                    let first = values.len() - children.len();
+                    char.apply(values.drain(first..))
+                }
+            };
+            values.push(value);
+        }
+        Ok(values.pop().unwrap())
+    }
+}
+
+/// Same as [`checked_eval_formula`], for formulas with variables.
+pub fn checked_eval_formula_with<A: Assignment + ?Sized>(
+    formula: &str,
+    assignment: &A,
+) -> Result<bool, MyError> {
+    Node::parse(formula)?.evaluate(assignment)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -89,4 +190,71 @@ mod tests {
 
         assert!(checked_eval_formula("1&").is_err());
     }
+
+    #[test]
+    fn assignments() {
+        let [a, b, x1] = ["A", "B", "x1"].map(Var::new);
+        let map = HashMap::from([(a, true), (b, false), (x1, true)]);
+        assert!(checked_eval_formula_with("AB|[x1]&", &map).unwrap());
+        assert!(checked_eval_formula_with("AB&!", &map).unwrap());
+        assert!(checked_eval_formula_with("01|", &map).unwrap());
+
+        let map = BTreeMap::from([(a, true)]);
+        assert!(matches!(
+            checked_eval_formula_with("A[x1]&", &map),
+            Err(MyError::UnsetVariable(v)) if v == x1
+        ));
+        assert!(matches!(
+            checked_eval_formula_with("A&", &map),
+            Err(MyError::MissingOperand('&', _))
+        ));
+
+        let closure = |var: Var| Some(var == b);
+        assert!(checked_eval_formula_with("AB>", &closure).unwrap());
+        assert!(!checked_eval_formula_with("BA>", &closure).unwrap());
+    }
+
+    #[test]
+    fn bitmask() {
+        let vars = ["A", "B", "C"].map(Var::new);
+        let tree = Node::parse("AB&C|").unwrap();
+        let results = (0..8)
+            .map(|bits| tree.evaluate(&Bitmask::new(&vars, bits)).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(results, [false, true, false, true, false, true, true, true]);
+        assert!(matches!(
+            tree.evaluate(&Bitmask::new(&vars[..2], 0)),
+            Err(MyError::UnsetVariable(v)) if v == Var::new("C")
+        ));
+    }
+
+    #[test]
+    fn evaluate_huge_formula() {
+        let tree = Node::parse(crate::bool_formula_ast::huge_formula()).unwrap();
+        let vars = ["A", "B"].map(Var::new);
+        // `!B&` flips the value when B is set, an even number of times
+        assert!(tree.evaluate(&Bitmask::new(&vars, 0b11)).unwrap());
+        assert!(!tree.evaluate(&Bitmask::new(&vars, 0b01)).unwrap());
+        assert!(!tree.evaluate(&Bitmask::new(&vars, 0b10)).unwrap());
+    }
+
+    #[test]
+    fn error_spans() {
+        assert!(matches!(
+            checked_eval_formula("10&1"),
+            Err(ParsingError::TooManyValues(Span { start: 0, end: 3 }))
+        ));
+        assert!(matches!(
+            checked_eval_formula("1!|"),
+            Err(ParsingError::MissingValue('|', Span { start: 2, end: 3 }))
+        ));
+        assert!(matches!(
+            checked_eval_formula("1ë&"),
+            Err(ParsingError::InvalidChar('ë', Span { start: 1, end: 3 }))
+        ));
+        assert!(matches!(
+            checked_eval_formula(""),
+            Err(ParsingError::Eof(Span { start: 0, end: 0 }))
+        ));
+    }
 }
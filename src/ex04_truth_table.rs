@@ -1,130 +1,696 @@
 use core::fmt;
+use std::ops::{BitAnd, BitOr, BitXor, Not, Range};
 
-use crate::bool_formula_ast::{MyError, Node};
+use thiserror::Error;
 
-#[derive(Debug, Default, PartialEq)]
+use crate::bit_parallel::ParallelVm;
+use crate::bool_formula_ast::{MyError, Node, Oper};
+use crate::bool_formula_infix::Notation;
+use crate::bytecode::Program;
+use crate::cube::Cube;
+use crate::diagnostic::{Diagnostic, Span, Spanned};
+use crate::ex02_gray_code::gray_code;
+use crate::ex03_boolean_evaluation::Assignment;
+use crate::variable::Var;
+
+/// Most variables a truth table can have, its 2^30 rows take 128 MiB.
+pub const MAX_VARIABLES: usize = 30;
+
+#[derive(Error, Debug)]
+pub enum TruthTableError {
+    #[error("too many variables: {0}")]
+    TooManyVariables(usize),
+    #[error("expected one bit per row, {1} rows, found {0} bits")]
+    RowCount(usize, u64),
+    #[error("row {0} is out of range, there are {1} rows")]
+    RowOutOfRange(u64, u64),
+    #[error(transparent)]
+    Formula(#[from] MyError),
+}
+
+impl Spanned for TruthTableError {
+    /// Only errors in the formula can be located
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::Formula(e) => e.span(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TruthTable {
-    variables: Vec<char>,
-    results: Vec<bool>,
-}
-
-fn recursive_truth_table_results(formula: &Node, vars: &[char]) -> Result<Vec<bool>, MyError> {
-    let mut result = vec![];
-    let var = vars[0];
-    for val in [false, true] {
-        let mut f = formula.clone();
-        f.partial_evaluate(var, val);
-        if vars.len() == 1 {
-            match f {
-                Node::Value(val) => result.push(val),
-                _ => return Err(MyError::UnsetVariable('_')),
-            }
-        } else {
-            result.append(&mut recursive_truth_table_results(&f, &vars[1..])?);
+    variables: Vec<Var>,
+    /// Result of row `i` in bit `i % 64` of `bits[i / 64]`, the bits past the last row are cleared
+    bits: Vec<u64>,
+}
+
+impl Default for TruthTable {
+    /// Table of `0`
+    fn default() -> Self {
+        Self {
+            variables: vec![],
+            bits: vec![0],
         }
     }
-    Ok(result)
 }
 
-/// Iterator over truth table entries in the form:
-/// `([b'0', b'1', ...], true)`
+/// Row of a [`TruthTable`], the values of the variables are the bits of its index
+/// with the first variable as most significant bit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Row<'a> {
+    variables: &'a [Var],
+    index: u64,
+    result: bool,
+}
+
+impl Row<'_> {
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    pub fn result(&self) -> bool {
+        self.result
+    }
+
+    /// Value of the `i`th variable of the table
+    pub fn value_at(&self, i: usize) -> bool {
+        self.index >> (self.variables.len() - 1 - i) & 1 == 1
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = (Var, bool)> + '_ {
+        (0..self.variables.len()).map(|i| (self.variables[i], self.value_at(i)))
+    }
+}
+
+impl Assignment for Row<'_> {
+    fn value(&self, var: Var) -> Option<bool> {
+        let i = self.variables.iter().position(|&v| v == var)?;
+        Some(self.value_at(i))
+    }
+}
+
+/// Iterator over the [`Row`]s of a truth table, in order
 pub struct TruthTableEntriesIterator<'a> {
     truth_table: &'a TruthTable,
-    i: usize,
+    rows: Range<u64>,
 }
 
-impl Iterator for TruthTableEntriesIterator<'_> {
-    type Item = (Vec<u8>, bool);
+impl<'a> Iterator for TruthTableEntriesIterator<'a> {
+    type Item = Row<'a>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let result = *self.truth_table.results.get(self.i)?;
-        let values = if self.i == 0 && self.truth_table.variables.is_empty() {
-            vec![]
-        } else {
-            format!(
-                "{:0width$b}",
-                self.i,
-                width = self.truth_table.variables.len()
-            )
-            .into_bytes()
-        };
-        self.i += 1;
+        self.rows.next().map(|i| self.truth_table.row(i))
+    }
 
-        Some((values, result))
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.rows.size_hint()
     }
 }
 
 impl TruthTable {
-    pub fn compute(formula: &str) -> Result<Self, MyError> {
-        let mut vars = formula
-            .chars()
-            .filter(|token| token.is_ascii_alphabetic())
-            .collect::<Vec<_>>();
-        vars.sort();
-        vars.dedup();
-
-        Self::compute_with_given_vars(formula, vars)
+    pub fn compute(formula: &str) -> Result<Self, TruthTableError> {
+        let formula = Node::parse(formula)?;
+        let variables = formula.variables();
+        Self::from_node_with_given_vars(formula, variables)
     }
 
     /// Allows comparign a simplified formula (with optimized out vars) to a full formula
-    pub fn compute_with_given_vars(formula: &str, variables: Vec<char>) -> Result<Self, MyError> {
-        let mut formula = Node::parse(formula)?;
-        let results = if variables.is_empty() {
-            formula.partial_evaluate('_', false);
-            match formula {
-                Node::Value(val) => vec![val],
-                _ => return Err(MyError::UnsetVariable('_')),
-            }
-        } else {
-            recursive_truth_table_results(&formula, &variables)?
-        };
+    pub fn compute_with_given_vars(
+        formula: &str,
+        variables: Vec<Var>,
+    ) -> Result<Self, TruthTableError> {
+        Self::from_node_with_given_vars(Node::parse(formula)?, variables)
+    }
 
-        Ok(Self { variables, results })
+    pub fn from_node(node: &Node) -> Result<Self, TruthTableError> {
+        Self::from_node_with_given_vars(node.clone(), node.variables())
     }
 
-    pub fn entries(&self) -> TruthTableEntriesIterator {
+    fn from_node_with_given_vars(
+        mut formula: Node,
+        variables: Vec<Var>,
+    ) -> Result<Self, TruthTableError> {
+        if variables.len() > MAX_VARIABLES {
+            return Err(TruthTableError::TooManyVariables(variables.len()));
+        }
+        // Fewer nodes to evaluate for every row
+        formula.flatten_mut();
+        let program = Program::compile_with_vars(&formula, variables.clone())?;
+        // 64 rows per pass, the blocks have the layout of `bits`
+        let bits = ParallelVm::<u64>::new(&program).blocks().collect();
+        Ok(Self { variables, bits })
+    }
+
+    /// Table over `variables` whose row `i` has result `f(i)`.
+    pub fn from_fn(
+        variables: Vec<Var>,
+        mut f: impl FnMut(u64) -> bool,
+    ) -> Result<Self, TruthTableError> {
+        let rows = Self::nb_rows(&variables)?;
+        let mut bits = vec![0u64; rows.div_ceil(64) as usize];
+        for row in (0..rows).filter(|&row| f(row)) {
+            bits[(row / 64) as usize] |= 1 << (row % 64);
+        }
+        Ok(Self { variables, bits })
+    }
+
+    /// Rows of a table over `variables`, checked before anything is allocated
+    fn nb_rows(variables: &[Var]) -> Result<u64, TruthTableError> {
+        if variables.len() > MAX_VARIABLES {
+            return Err(TruthTableError::TooManyVariables(variables.len()));
+        }
+        Ok(1 << variables.len())
+    }
+
+    /// Table over `variables` whose row `i` has result `bits[i]`.
+    pub fn from_bits(variables: Vec<Var>, bits: &[bool]) -> Result<Self, TruthTableError> {
+        let rows = Self::nb_rows(&variables)?;
+        if bits.len() as u64 != rows {
+            return Err(TruthTableError::RowCount(bits.len(), rows));
+        }
+        Self::from_fn(variables, |row| bits[row as usize])
+    }
+
+    /// Table over `variables` whose result is `1` for the rows in `minterms` only.
+    pub fn from_minterms(variables: Vec<Var>, minterms: &[u64]) -> Result<Self, TruthTableError> {
+        let rows = Self::nb_rows(&variables)?;
+        if let Some(&minterm) = minterms.iter().find(|&&minterm| minterm >= rows) {
+            return Err(TruthTableError::RowOutOfRange(minterm, rows));
+        }
+        let mut table = Self::from_fn(variables, |_| false)?;
+        for &minterm in minterms {
+            table.bits[(minterm / 64) as usize] |= 1 << (minterm % 64);
+        }
+        Ok(table)
+    }
+
+    /// Indices of the rows whose result is `1`
+    pub fn minterms(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.rows()).filter(|&row| self.get(row))
+    }
+
+    /// Indices of the rows whose result is `0`
+    pub fn maxterms(&self) -> impl Iterator<Item = u64> + '_ {
+        (0..self.rows()).filter(|&row| !self.get(row))
+    }
+
+    /// Canonical disjunctive normal form: the `|` of the minterms,
+    /// `&`s of every variable negated where the row has a `0`.
+    pub fn to_dnf(&self) -> Node {
+        let terms = self
+            .minterms()
+            .map(|row| self.term(row, Oper::Conjunction))
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Node::Value(false);
+        }
+        Node::n_ary(Oper::Disjunction, terms)
+    }
+
+    /// Canonical conjunctive normal form: the `&` of the maxterms,
+    /// `|`s of every variable negated where the row has a `1`.
+    pub fn to_cnf(&self) -> Node {
+        let terms = self
+            .maxterms()
+            .map(|row| self.term(row, Oper::Disjunction))
+            .collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Node::Value(true);
+        }
+        Node::n_ary(Oper::Conjunction, terms)
+    }
+
+    /// Minterm (`&`) or maxterm (`|`) of `row`
+    fn term(&self, row: u64, op: Oper) -> Node {
+        if self.variables.is_empty() {
+            // The only row
+            return Node::Value(op == Oper::Conjunction);
+        }
+        let literals = self
+            .row(row)
+            .values()
+            .map(|(var, value)| {
+                if value == (op == Oper::Conjunction) {
+                    Node::Variable(var)
+                } else {
+                    Node::Neg(Box::new(Node::Variable(var)))
+                }
+            })
+            .collect();
+        Node::n_ary(op, literals)
+    }
+
+    pub fn entries(&self) -> TruthTableEntriesIterator<'_> {
         TruthTableEntriesIterator {
-            i: 0,
             truth_table: self,
+            rows: 0..self.rows(),
         }
     }
 
-    #[allow(dead_code)]
-    pub fn variables(&self) -> &[char] {
+    pub fn variables(&self) -> &[Var] {
         &self.variables
     }
+
+    /// Number of rows, `2^variables().len()`
+    pub fn rows(&self) -> u64 {
+        1 << self.variables.len()
+    }
+
+    /// Result of the row at `index`
+    pub fn get(&self, index: u64) -> bool {
+        assert!(index < self.rows(), "row {index} out of bounds");
+        self.bits[(index / 64) as usize] >> (index % 64) & 1 == 1
+    }
+
+    pub fn row(&self, index: u64) -> Row<'_> {
+        Row {
+            variables: &self.variables,
+            index,
+            result: self.get(index),
+        }
+    }
+
+    /// Result for the values `assignment` gives to the variables of the table.
+    pub fn lookup<A: Assignment + ?Sized>(&self, assignment: &A) -> Result<bool, MyError> {
+        let index = self.variables.iter().try_fold(0u64, |index, &var| {
+            let value = assignment.value(var).ok_or(MyError::UnsetVariable(var))?;
+            Ok::<_, MyError>(index << 1 | value as u64)
+        })?;
+        Ok(self.get(index))
+    }
+
+    /// Number of rows whose result is `1`
+    pub fn count_ones(&self) -> u64 {
+        self.bits.iter().map(|word| word.count_ones() as u64).sum()
+    }
+
+    /// Whether every row of `cube` has result `1`
+    pub fn covers_cube(&self, cube: &Cube) -> bool {
+        let (lanes, mut words) = self.cube_words(cube);
+        words.all(|word| self.bits[word as usize] & lanes == lanes)
+    }
+
+    /// Whether some row of `cube` has result `1`
+    pub fn intersects_cube(&self, cube: &Cube) -> bool {
+        let (lanes, mut words) = self.cube_words(cube);
+        words.any(|word| self.bits[word as usize] & lanes != 0)
+    }
+
+    /// Disjoint cubes of the rows with result `1`, from halving the table one variable at a time
+    /// until a half has only `1`s or no `1`.
+    pub fn cubes(&self) -> Vec<Cube> {
+        let mut cubes = Vec::new();
+        let mut stack = vec![Cube::universe()];
+        while let Some(cube) = stack.pop() {
+            if !self.intersects_cube(&cube) {
+                continue;
+            }
+            if self.covers_cube(&cube) {
+                cubes.push(cube);
+                continue;
+            }
+            // A row is covered or not, so a variable is left
+            let bit = (0..self.variables.len() as u32)
+                .rev()
+                .find(|bit| cube.mask() >> bit & 1 == 0)
+                .unwrap();
+            let mask = cube.mask() | 1 << bit;
+            stack.push(Cube::new(cube.bits() | 1 << bit, mask));
+            stack.push(Cube::new(cube.bits(), mask));
+        }
+        cubes
+    }
+
+    /// The rows of `cube` are the `lanes` of the `bits` words it yields
+    fn cube_words(&self, cube: &Cube) -> (u64, impl Iterator<Item = u64> + use<>) {
+        let nb_vars = self.variables.len();
+        let (low_mask, low_bits) = (cube.mask() & 63, cube.bits() & 63);
+        let lanes = (0..self.rows().min(64))
+            .filter(|lane| lane & low_mask == low_bits)
+            .fold(0, |lanes, lane| lanes | 1 << lane);
+        let words = Cube::new(cube.bits() >> 6, cube.mask() >> 6).rows(nb_vars.saturating_sub(6));
+        (lanes, words)
+    }
+
+    /// Same function over `variables`, in that order.
+    /// They may include variables the table doesn't have, but not miss one it has.
+    pub fn with_variables(&self, variables: Vec<Var>) -> Result<Self, TruthTableError> {
+        if variables == self.variables {
+            return Ok(self.clone());
+        }
+        // Position of each of our variables in the index of a row of the new table
+        let shifts = self
+            .variables
+            .iter()
+            .map(|&var| {
+                let i = variables.iter().position(|&v| v == var);
+                Ok(variables.len() - 1 - i.ok_or(MyError::UnsetVariable(var))?)
+            })
+            .collect::<Result<Vec<_>, MyError>>()?;
+        Self::from_fn(variables, |row| {
+            self.get(
+                shifts
+                    .iter()
+                    .fold(0, |index, &shift| index << 1 | row >> shift & 1),
+            )
+        })
+    }
+
+    /// Variables of both tables: ours followed by the others of `rhs`,
+    /// sorted if both tables have sorted variables.
+    fn union_variables(&self, rhs: &Self) -> Vec<Var> {
+        let mut variables = self.variables.clone();
+        variables.extend(
+            rhs.variables
+                .iter()
+                .filter(|var| !self.variables.contains(var)),
+        );
+        if self.variables.is_sorted() && rhs.variables.is_sorted() {
+            variables.sort();
+        }
+        variables
+    }
+
+    /// Fails if the tables have more than [`MAX_VARIABLES`] variables together.
+    fn zip_with(&self, rhs: &Self, op: impl Fn(u64, u64) -> u64) -> Result<Self, TruthTableError> {
+        if self.variables != rhs.variables {
+            let variables = self.union_variables(rhs);
+            let lhs = self.with_variables(variables.clone())?;
+            return lhs.zip_with(&rhs.with_variables(variables)?, op);
+        }
+        let mut table = Self {
+            variables: self.variables.clone(),
+            bits: self
+                .bits
+                .iter()
+                .zip(&rhs.bits)
+                .map(|(&a, &b)| op(a, b))
+                .collect(),
+        };
+        table.clear_past_last_row();
+        Ok(table)
+    }
+
+    fn clear_past_last_row(&mut self) {
+        if self.rows() < 64 {
+            self.bits[0] &= (1 << self.rows()) - 1;
+        }
+    }
 }
 
-impl fmt::Display for TruthTable {
+macro_rules! impl_binary_op {
+    ($($trait:ident $method:ident $try_method:ident $op:tt),*) => {$(
+        impl TruthTable {
+            #[doc = concat!("`", stringify!($op), "` over the union of the variables of both tables.")]
+            ///
+            /// # Errors
+            ///
+            /// [`TruthTableError::TooManyVariables`] if they have more than [`MAX_VARIABLES`] together.
+            pub fn $try_method(&self, rhs: &Self) -> Result<Self, TruthTableError> {
+                self.zip_with(rhs, |a, b| a $op b)
+            }
+        }
+
+        /// Over the union of the variables of both tables
+        ///
+        /// # Panics
+        ///
+        #[doc = concat!("If they have more than [`MAX_VARIABLES`] together, see [`TruthTable::", stringify!($try_method), "`].")]
+        impl $trait for &TruthTable {
+            type Output = TruthTable;
+            fn $method(self, rhs: Self) -> TruthTable {
+                self.$try_method(rhs).unwrap_or_else(|e| panic!("{e}"))
+            }
+        }
+
+        /// See the impl for `&TruthTable`
+
+        impl $trait for TruthTable {
+            type Output = Self;
+            fn $method(self, rhs: Self) -> Self {
+                &self $op &rhs
+            }
+        }
+    )*};
+}
+
+impl_binary_op!(BitAnd bitand try_and &, BitOr bitor try_or |, BitXor bitxor try_xor ^);
+
+impl Not for &TruthTable {
+    type Output = TruthTable;
+    fn not(self) -> TruthTable {
+        let mut table = TruthTable {
+            variables: self.variables.clone(),
+            bits: self.bits.iter().map(|word| !word).collect(),
+        };
+        table.clear_past_last_row();
+        table
+    }
+}
+
+impl Not for TruthTable {
+    type Output = Self;
+    fn not(self) -> Self {
+        !&self
+    }
+}
+
+/// Layouts of [`TruthTable::render`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// `| A | B | = |` columns aligned on the variable names, used by `Display`
+    #[default]
+    Markdown,
+    /// `A,B,result` header then one line per row
+    Csv,
+    /// `{"variables": ["A", "B"], "rows": [[0, 0, 0], ...]}`,
+    /// a row holds the values of the variables then the result
+    Json,
+    /// LaTeX `tabular` environment
+    Latex,
+    /// Result column as a hexadecimal number whose bit `i` is the result of row `i`, `8` for `AB&`
+    Hex,
+}
+
+/// Rendering of a [`TruthTable`], see [`TruthTable::render`].
+pub struct Rendered<'a> {
+    view: View<'a>,
+    format: Format,
+}
+
+impl fmt::Display for Rendered<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.view.write(f, self.format)
+    }
+}
+
+impl TruthTable {
+    pub fn render(&self, format: Format) -> Rendered<'_> {
+        self.view().render(format)
+    }
+
+    /// All the rows, in order, to be narrowed down by the methods of [`View`].
+    pub fn view(&self) -> View<'_> {
+        View {
+            table: self,
+            gray_code: false,
+            filters: Vec::new(),
+        }
+    }
+}
+
+type RowFilter<'a> = Box<dyn Fn(&Row<'a>) -> bool + 'a>;
+
+/// Rows of a [`TruthTable`] passing some filters, in binary or Gray code order.
+pub struct View<'a> {
+    table: &'a TruthTable,
+    gray_code: bool,
+    filters: Vec<RowFilter<'a>>,
+}
+
+impl<'a> View<'a> {
+    /// Rows whose result is `1`
+    pub fn only_true(self) -> Self {
+        self.filter(|row| row.result())
+    }
+
+    /// Rows whose result is `0`
+    pub fn only_false(self) -> Self {
+        self.filter(|row| !row.result())
+    }
+
+    /// Rows where `var` is `value`, none if the table doesn't have `var`
+    pub fn with_value(self, var: Var, value: bool) -> Self {
+        self.filter(move |row| row.value(var) == Some(value))
+    }
+
+    /// Rows for which `predicate` is true, on top of the previous filters
+    pub fn filter(mut self, predicate: impl Fn(&Row<'a>) -> bool + 'a) -> Self {
+        self.filters.push(Box::new(predicate));
+        self
+    }
+
+    /// Rows in Gray code order, each differs from the previous one by one variable
+    pub fn gray_code(mut self) -> Self {
+        self.gray_code = true;
+        self
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item = Row<'a>> + '_ {
+        (0..self.table.rows())
+            .map(|i| {
+                let index = if self.gray_code {
+                    gray_code(i as u32) as u64
+                } else {
+                    i
+                };
+                self.table.row(index)
+            })
+            .filter(|row| self.filters.iter().all(|filter| filter(row)))
+    }
+
+    fn is_whole_table(&self) -> bool {
+        !self.gray_code && self.filters.is_empty()
+    }
+
+    pub fn render(self, format: Format) -> Rendered<'a> {
+        Rendered { view: self, format }
+    }
+}
+
+impl View<'_> {
+    fn write(&self, f: &mut fmt::Formatter<'_>, format: Format) -> fmt::Result {
+        match format {
+            Format::Markdown => self.markdown(f),
+            Format::Csv => self.csv(f),
+            Format::Json => self.json(f),
+            Format::Latex => self.latex(f),
+            Format::Hex => self.hex(f),
+        }
+    }
+
+    fn markdown(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = self.table;
         // Print header
-        for &v in self.variables.iter() {
+        for &v in table.variables.iter() {
             write!(f, "| {} ", v)?;
         }
         writeln!(f, "| = |")?;
 
         // Print separator
-        for _ in self.variables.iter() {
-            write!(f, "|---")?;
+        for v in table.variables.iter() {
+            write!(f, "|-{}-", "-".repeat(v.name().len()))?;
         }
         writeln!(f, "|---|")?;
 
         // Print rows
-        for (values, result) in self.entries() {
-            for c in values {
-                write!(f, "| {} ", c as char)?;
+        for row in self.rows() {
+            for (v, value) in row.values() {
+                write!(
+                    f,
+                    "| {:width$} ",
+                    char::from(b'0' + value as u8),
+                    width = v.name().len()
+                )?;
             }
-            writeln!(f, "| {} |", ((result as u8) + b'0') as char)?;
+            writeln!(f, "| {} |", row.result() as u8)?;
         }
+        Ok(())
+    }
 
+    fn csv(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // Variable names never need quoting
+        for v in self.table.variables.iter() {
+            write!(f, "{v},")?;
+        }
+        writeln!(f, "result")?;
+        for row in self.rows() {
+            for (_, value) in row.values() {
+                write!(f, "{},", value as u8)?;
+            }
+            writeln!(f, "{}", row.result() as u8)?;
+        }
         Ok(())
     }
+
+    fn json(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{{\n  \"variables\": [")?;
+        for (i, v) in self.table.variables.iter().enumerate() {
+            let sep = if i == 0 { "" } else { ", " };
+            write!(f, "{sep}\"{v}\"")?;
+        }
+        write!(f, "],\n  \"rows\": [")?;
+        for (i, row) in self.rows().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{sep}\n    [")?;
+            for (_, value) in row.values() {
+                write!(f, "{}, ", value as u8)?;
+            }
+            write!(f, "{}]", row.result() as u8)?;
+        }
+        writeln!(f, "\n  ]\n}}")
+    }
+
+    fn latex(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variables = &self.table.variables;
+        let separator = if variables.is_empty() { "" } else { "|" };
+        writeln!(
+            f,
+            "\\begin{{tabular}}{{|{}{separator}c|}}\n\\hline",
+            "c|".repeat(variables.len())
+        )?;
+        for &v in variables.iter() {
+            write!(f, "${}$ & ", Node::Variable(v).infix(Notation::Latex))?;
+        }
+        writeln!(f, "$=$ \\\\\n\\hline")?;
+        for row in self.rows() {
+            for (_, value) in row.values() {
+                write!(f, "{} & ", value as u8)?;
+            }
+            writeln!(f, "{} \\\\", row.result() as u8)?;
+        }
+        writeln!(f, "\\hline\n\\end{{tabular}}")
+    }
+
+    fn hex(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let table = self.table;
+        if self.is_whole_table() && table.bits.len() > 1 {
+            return table
+                .bits
+                .iter()
+                .rev()
+                .try_for_each(|word| write!(f, "{word:016x}"));
+        }
+        // Bit `i` is the result of the `i`th row of the view
+        let results = self.rows().map(|row| row.result()).collect::<Vec<_>>();
+        for digit in results.chunks(4).rev() {
+            let nibble = digit
+                .iter()
+                .rev()
+                .fold(0, |nibble, &result| nibble << 1 | result as u8);
+            write!(f, "{nibble:x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for View<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, Format::Markdown)
+    }
+}
+
+impl fmt::Display for TruthTable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.render(Format::Markdown).fmt(f)
+    }
 }
 
 pub fn print_truth_table(formula: &str) {
     match TruthTable::compute(formula) {
         Ok(tt) => print!("{}", tt),
-        Err(e) => eprintln!("{}", e),
+        Err(e) => eprint!("{}", Diagnostic::new(formula, &e)),
     }
 }
 
@@ -141,9 +707,262 @@ mod tests {
         );
         assert!(TruthTable::compute("AB&C|&").is_err());
     }
+
+    #[test]
+    fn test_truth_table_named_variables() {
+        let res = TruthTable::compute("[x2][x10]|").unwrap().to_string();
+        assert_eq!(
+            res,
+            "| x2 | x10 | = |\n|----|-----|---|\n| 0  | 0   | 0 |\n| 0  | 1   | 1 |\n| 1  | 0   | 1 |\n| 1  | 1   | 1 |\n"
+        );
+        assert!(matches!(
+            TruthTable::compute_with_given_vars("A[x]&", vec![Var::from('A')]),
+            Err(TruthTableError::Formula(MyError::UnsetVariable(v))) if v == Var::new("x")
+        ));
+    }
     #[test]
     fn test_truth_table_no_var() {
         let res = TruthTable::compute("0!").unwrap().to_string();
         assert_eq!(res, "| = |\n|---|\n| 1 |\n");
     }
+
+    #[test]
+    fn rows() {
+        let table = TruthTable::compute("AB>C&").unwrap();
+        assert_eq!(table.rows(), 8);
+        assert_eq!(table.count_ones(), 3);
+        assert!(table.get(0b001));
+        assert!(!table.get(0b101));
+        let row = table.row(0b111);
+        assert!(row.result());
+        assert!(row.value_at(0));
+        assert_eq!(row.value(Var::from('B')), Some(true));
+
+        let assignment = |var: Var| Some(var != Var::from('B'));
+        assert!(!table.lookup(&assignment).unwrap());
+        assert!(matches!(
+            table.lookup(&|var: Var| (var == Var::from('A')).then_some(true)),
+            Err(MyError::UnsetVariable(v)) if v == Var::from('B')
+        ));
+        assert!(
+            table
+                .entries()
+                .all(|row| table.lookup(&row).unwrap() == row.result())
+        );
+    }
+
+    #[test]
+    fn boolean_algebra() {
+        let a = TruthTable::compute("A").unwrap();
+        let b = TruthTable::compute("B").unwrap();
+        let c = TruthTable::compute("C").unwrap();
+        assert_eq!(&a & &b, TruthTable::compute("AB&").unwrap());
+        assert_eq!(&b | &a, TruthTable::compute("AB|").unwrap());
+        assert_eq!(!(&a ^ &b) | c, TruthTable::compute("AB=C|").unwrap());
+        assert_eq!(!&a, TruthTable::compute("A!").unwrap());
+        assert_eq!(!TruthTable::default(), TruthTable::compute("1").unwrap());
+
+        let ba = TruthTable::compute_with_given_vars("AB>", vec![Var::from('B'), Var::from('A')])
+            .unwrap();
+        assert_eq!(
+            ba.to_string(),
+            "| B | A | = |\n|---|---|---|\n| 0 | 0 | 1 |\n| 0 | 1 | 0 |\n| 1 | 0 | 1 |\n| 1 | 1 | 1 |\n"
+        );
+        assert_eq!(
+            ba.with_variables(vec![Var::from('A'), Var::from('B')])
+                .unwrap(),
+            TruthTable::compute("AB>").unwrap()
+        );
+        assert_eq!((&ba & &a).variables(), [Var::from('B'), Var::from('A')]);
+        assert_eq!(
+            &ba & &a,
+            TruthTable::compute_with_given_vars("AB&", ba.variables().to_vec()).unwrap()
+        );
+        assert_eq!(a.try_or(&b).unwrap(), &a | &b);
+
+        // 16 and 15 variables, 31 together
+        let xor = |range: std::ops::Range<usize>| {
+            let vars = range.map(|i| format!("[x{i}]")).collect::<Vec<_>>();
+            let formula = vars.concat() + &"^".repeat(vars.len() - 1);
+            TruthTable::compute(&formula).unwrap()
+        };
+        let (low, high) = (xor(0..16), xor(16..31));
+        assert!(matches!(
+            low.try_and(&high),
+            Err(TruthTableError::TooManyVariables(31))
+        ));
+        assert!(matches!(
+            high.try_xor(&low),
+            Err(TruthTableError::TooManyVariables(31))
+        ));
+    }
+
+    #[test]
+    fn many_variables() {
+        let vars = (0..22).map(|i| format!("[x{i}]")).collect::<Vec<_>>();
+        let table = TruthTable::compute(&format!("{}{}", vars.concat(), "^".repeat(21))).unwrap();
+        assert_eq!(table.count_ones(), 1 << 21);
+        assert!(table.get(0b1));
+        assert!(!table.get(0b11 << 20));
+
+        let vars = (0..=MAX_VARIABLES)
+            .map(|i| format!("[x{i}]"))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            TruthTable::compute(&format!("{}{}", vars.concat(), "|".repeat(MAX_VARIABLES))),
+            Err(TruthTableError::TooManyVariables(n)) if n == MAX_VARIABLES + 1
+        ));
+    }
+
+    #[test]
+    fn formats() {
+        let table = TruthTable::compute("[x1]B&").unwrap();
+        assert_eq!(
+            table.render(Format::Csv).to_string(),
+            "B,x1,result\n0,0,0\n0,1,0\n1,0,0\n1,1,1\n"
+        );
+        assert_eq!(
+            table.render(Format::Json).to_string(),
+            "{\n  \"variables\": [\"B\", \"x1\"],\n  \"rows\": [\n    [0, 0, 0],\n    [0, 1, 0],\n    [1, 0, 0],\n    [1, 1, 1]\n  ]\n}\n"
+        );
+        assert_eq!(
+            table.render(Format::Latex).to_string(),
+            "\\begin{tabular}{|c|c||c|}\n\\hline\n$B$ & $x_{1}$ & $=$ \\\\\n\\hline\n0 & 0 & 0 \\\\\n0 & 1 & 0 \\\\\n1 & 0 & 0 \\\\\n1 & 1 & 1 \\\\\n\\hline\n\\end{tabular}\n"
+        );
+        assert_eq!(table.render(Format::Hex).to_string(), "8");
+        assert_eq!(
+            table.render(Format::Markdown).to_string(),
+            table.to_string()
+        );
+
+        let hex = |formula| {
+            TruthTable::compute(formula)
+                .unwrap()
+                .render(Format::Hex)
+                .to_string()
+        };
+        assert_eq!(hex("1"), "1");
+        assert_eq!(hex("A!"), "1");
+        assert_eq!(hex("AB|C|"), "fe");
+        assert_eq!(hex("ABCDEFG&&&&&&"), format!("8{}", "0".repeat(31)));
+        assert_eq!(
+            TruthTable::compute("0")
+                .unwrap()
+                .render(Format::Json)
+                .to_string(),
+            "{\n  \"variables\": [],\n  \"rows\": [\n    [0]\n  ]\n}\n"
+        );
+    }
+
+    #[test]
+    fn views() {
+        let table = TruthTable::compute("AB|C&").unwrap();
+        let indices = |view: View| view.rows().map(|row| row.index()).collect::<Vec<_>>();
+        assert_eq!(indices(table.view().only_true()), [3, 5, 7]);
+        assert_eq!(indices(table.view().only_false()), [0, 1, 2, 4, 6]);
+        assert_eq!(
+            indices(table.view().with_value(Var::from('A'), true)),
+            [4, 5, 6, 7]
+        );
+        assert_eq!(indices(table.view().gray_code()), [0, 1, 3, 2, 6, 7, 5, 4]);
+        assert_eq!(
+            indices(
+                table
+                    .view()
+                    .gray_code()
+                    .only_true()
+                    .filter(|row| row.index() != 7)
+            ),
+            [3, 5]
+        );
+        assert!(indices(table.view().with_value(Var::from('D'), true)).is_empty());
+
+        let view = table.view().gray_code().only_true();
+        assert_eq!(
+            view.to_string(),
+            "| A | B | C | = |\n|---|---|---|---|\n| 0 | 1 | 1 | 1 |\n| 1 | 1 | 1 | 1 |\n| 1 | 0 | 1 | 1 |\n"
+        );
+        assert_eq!(
+            view.render(Format::Csv).to_string(),
+            "A,B,C,result\n0,1,1,1\n1,1,1,1\n1,0,1,1\n"
+        );
+        // Bits of the results in the order of the view
+        assert_eq!(
+            table.view().gray_code().render(Format::Hex).to_string(),
+            "64"
+        );
+        assert_eq!(
+            table.view().only_false().render(Format::Hex).to_string(),
+            "00"
+        );
+    }
+
+    #[test]
+    fn synthesis() {
+        let [a, b, c] = ['A', 'B', 'C'].map(Var::from);
+        let table = TruthTable::from_minterms(vec![a, b, c], &[1, 6, 7]).unwrap();
+        assert_eq!(
+            table,
+            TruthTable::from_bits(
+                vec![a, b, c],
+                &[false, true, false, false, false, false, true, true]
+            )
+            .unwrap()
+        );
+        assert_eq!(table.minterms().collect::<Vec<_>>(), [1, 6, 7]);
+        let mut cubes = table.cubes();
+        cubes.sort();
+        assert_eq!(cubes, [Cube::minterm(1, 3), Cube::new(0b110, 0b110)]);
+        assert_eq!(table.to_dnf().to_string(), "A!B!C&&ABC!&&ABC&&||");
+        assert_eq!(
+            table.to_cnf().to_string(),
+            "ABC||AB!C||AB!C!||A!BC||A!BC!||&&&&"
+        );
+        assert_eq!(TruthTable::from_node(&table.to_dnf()).unwrap(), table);
+        assert_eq!(TruthTable::from_node(&table.to_cnf()).unwrap(), table);
+
+        let constant = |value| TruthTable::from_bits(vec![], &[value]).unwrap();
+        assert_eq!(constant(true).to_dnf(), Node::Value(true));
+        assert_eq!(constant(true).to_cnf(), Node::Value(true));
+        assert_eq!(constant(false).to_dnf(), Node::Value(false));
+        assert_eq!(constant(false).to_cnf(), Node::Value(false));
+        let never = TruthTable::from_minterms(vec![a], &[]).unwrap();
+        assert_eq!(never.to_dnf(), Node::Value(false));
+        assert_eq!(never.to_cnf().to_string(), "AA!&");
+
+        assert!(matches!(
+            TruthTable::from_bits(vec![a], &[true]),
+            Err(TruthTableError::RowCount(1, 2))
+        ));
+        assert!(matches!(
+            TruthTable::from_minterms(vec![a, b], &[1, 4]),
+            Err(TruthTableError::RowOutOfRange(4, 4))
+        ));
+        // Rejected before the 2^30 rows are enumerated
+        let many = (0..MAX_VARIABLES)
+            .map(|i| Var::new(&format!("x{i}")))
+            .collect::<Vec<_>>();
+        assert!(matches!(
+            TruthTable::from_bits(many.clone(), &[true]),
+            Err(TruthTableError::RowCount(1, rows)) if rows == 1 << MAX_VARIABLES
+        ));
+        assert!(matches!(
+            TruthTable::from_minterms(many, &[u64::MAX]),
+            Err(TruthTableError::RowOutOfRange(u64::MAX, _))
+        ));
+    }
+
+    #[test]
+    fn synthesis_random() {
+        for _ in 0..50 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            let table = TruthTable::from_node(&tree).unwrap();
+            let dnf = TruthTable::from_node(&table.to_dnf()).unwrap();
+            let cnf = TruthTable::from_node(&table.to_cnf()).unwrap();
+            // Variables that don't matter may be gone
+            let variables = table.variables().to_vec();
+            assert_eq!(dnf.with_variables(variables.clone()).unwrap(), table);
+            assert_eq!(cnf.with_variables(variables).unwrap(), table);
+        }
+    }
 }
@@ -13,9 +13,14 @@ impl Node {
                 Node::Operator(Op { char: op, children }) => {
                     match op {
                         Oper::ExclusiveDisjunction => {
+                            // `ABC^^` is `A` xor `BC^`
+                            if children.len() > 2 {
+                                let rest = children.split_off(1);
+                                children.push(Node::Operator(Op::new(*op, rest)));
+                            }
                             // rm exclusive disjunction
                             *op = Oper::Conjunction;
-                            *children = Box::new([
+                            *children = vec![
                                 Node::Operator(Op {
                                     char: Oper::Disjunction,
                                     children: children.clone(),
@@ -24,14 +29,14 @@ impl Node {
                                     char: Oper::Conjunction,
                                     children: children.clone(),
                                 }))),
-                            ]);
+                            ];
                         }
                         Oper::Equivalence => {
                             // rm equivalence
                             let mut children_rev = children.clone();
                             children_rev.reverse();
                             *op = Oper::Conjunction;
-                            *children = Box::new([
+                            *children = vec![
                                 Node::Operator(Op {
                                     char: Oper::MaterialCondition,
                                     children: children.clone(),
@@ -40,21 +45,17 @@ impl Node {
                                     char: Oper::MaterialCondition,
                                     children: children_rev,
                                 }),
-                            ]);
+                            ];
                         }
                         Oper::MaterialCondition => {
                             // rm material condition
                             *op = Oper::Disjunction;
-                            *children = Box::new([
-                                Node::Neg(Box::new(children[0].clone())),
-                                children[1].clone(),
-                            ]);
+                            // `A!B>` is `AB|`, not `A!!B|`
+                            children[0].neg();
                         }
                         Oper::Conjunction | Oper::Disjunction => (),
                     }
-                    let (left, right) = children.split_at_mut(1);
-                    stack.push(&mut left[0]);
-                    stack.push(&mut right[0]);
+                    stack.extend(children.iter_mut());
                 }
                 Node::Value(_) | Node::Variable(_) => (),
             }
@@ -65,13 +66,16 @@ impl Node {
     ///
     /// Using De Morgan's equivalences
     pub fn to_negation_normal_form_mut(&mut self) {
-        if let Node::Neg(child) = self {
-            if let Node::Operator(
-                op @ Op {
-                    char: Oper::Conjunction | Oper::Disjunction,
-                    ..
-                },
-            ) = &mut **child
+        let mut stack: Vec<&mut Node> = vec![self];
+
+        while let Some(node) = stack.pop() {
+            if let Node::Neg(child) = node
+                && let Node::Operator(
+                    op @ Op {
+                        char: Oper::Conjunction | Oper::Disjunction,
+                        ..
+                    },
+                ) = &mut **child
             {
                 for gc in op.children.iter_mut() {
                     gc.neg();
@@ -81,18 +85,9 @@ impl Node {
                 } else {
                     op.char = Oper::Conjunction
                 }
-                *self = mem::take(child);
-            }
-        }
-        match self {
-            Self::Neg(child) => {
-                child.to_negation_normal_form_mut();
+                *node = mem::take(child);
             }
-            Node::Operator(Op { children, .. }) => {
-                children[0].to_negation_normal_form_mut();
-                children[1].to_negation_normal_form_mut();
-            }
-            Node::Value(_) | Node::Variable(_) => (),
+            stack.extend(node.children_mut());
         }
     }
 }
@@ -118,6 +113,7 @@ pub fn negation_normal_form(formula: &str) -> String {
 mod tests {
     use super::*;
     use crate::ex04_truth_table::TruthTable;
+    use crate::variable::Var;
 
     /// returns the NNF of `formula`
     ///
@@ -136,7 +132,7 @@ mod tests {
                     );
                 }
                 Node::Variable(v) => {
-                    assert!(v.is_ascii_uppercase(), "Invalid variable: {v}");
+                    assert!(Var::is_valid_name(v.name()), "Invalid variable: {v}");
                 }
                 Node::Value(_) => (),
                 Node::Neg(ref child) => {
@@ -163,6 +159,7 @@ mod tests {
     #[test]
     fn material_conditions() {
         assert_eq!(negation_normal_form("AB>"), "A!B|");
+        assert_eq!(negation_normal_form("A!B>"), "AB|");
     }
 
     #[test]
@@ -186,6 +183,14 @@ mod tests {
         assert_correct_nnf("AB=C=A=E=A=A=A=A=D=B=B=B=A^B&!C>");
     }
 
+    #[test]
+    fn named_variables() {
+        assert_eq!(
+            assert_correct_nnf("[x1][carry_3]^!"),
+            "[x1]![carry_3]!&[x1][carry_3]&|"
+        );
+    }
+
     #[test]
     fn smoke_test_random() {
         for _ in 0..100 {
@@ -194,4 +199,30 @@ mod tests {
             assert_correct_nnf(&formula);
         }
     }
+
+    #[test]
+    fn n_ary() {
+        let mut tree = Node::parse("AB^C^!DE|F|&").unwrap();
+        tree.flatten_mut();
+        tree.to_primitive_connectives_mut();
+        tree.to_negation_normal_form_mut();
+        assert_eq!(assert_correct_nnf(&tree.to_string()), tree.to_string());
+        assert_eq!(
+            TruthTable::compute(&tree.to_string()).unwrap(),
+            TruthTable::compute("AB^C^!DE|F|&").unwrap()
+        );
+        assert!(tree.to_string().ends_with("DEF||&"));
+    }
+
+    #[test]
+    fn huge_formula() {
+        let formula = crate::bool_formula_ast::huge_formula();
+        let mut tree = Node::parse(formula.as_str()).unwrap();
+        assert_eq!(tree.to_string(), formula);
+        tree.to_primitive_connectives_mut();
+        tree.to_negation_normal_form_mut();
+        let nnf = tree.to_string();
+        // Only variables are negated
+        assert!(!nnf.contains("&!") && !nnf.contains("|!") && !nnf.contains("!!"));
+    }
 }
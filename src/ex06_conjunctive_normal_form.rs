@@ -1,194 +1,191 @@
 //! <https://en.wikipedia.org/wiki/Conjunctive_normal_form>
 use crate::bool_formula_ast::{MyError, Node, Op, Oper};
+use crate::clause::{Clause, Cnf, Literal};
+use std::collections::HashMap;
 use std::mem;
 
-impl Op {
-    /// Returns the deep children of chained operators.
-    fn op_depth_first_search(&mut self, is_top_level_call: bool, ops: &mut (Vec<Node>, Vec<Node>)) {
-        let children_match = self
-            .children
-            .iter()
-            .map(|child| matches!(child, Node::Operator(child_op) if child_op.char == self.char))
-            .collect::<Vec<_>>();
-
-        if is_top_level_call && !children_match[0] && !children_match[1] {
-            return;
-        }
-        match &mut self.children[0] {
-            Node::Operator(child_op) if child_op.char == self.char => {
-                child_op.op_depth_first_search(false, ops);
-            }
-            child => ops.0.push(mem::take(child)),
-        }
-        match &mut self.children[1] {
-            Node::Operator(child_op) if child_op.char == self.char => {
-                child_op.op_depth_first_search(false, ops);
-            }
-            child => ops.1.push(mem::take(child)),
-        }
-    }
-
-    /// Helper for `Self::op_depth_first_search`.
-    fn op_depth_first_search_chained(&mut self) -> Vec<Node> {
-        let mut ops = (Vec::with_capacity(100), Vec::with_capacity(100));
-        self.op_depth_first_search(true, &mut ops);
-        let (mut left, mut right) = ops;
-        left.append(&mut right);
-        left
+impl Node {
+    pub fn is_conjunctive_normal_form(&mut self, accept_conjunctions: bool) -> bool {
+        self.is_normal_form(Oper::Conjunction, Oper::Disjunction, accept_conjunctions)
     }
 
-    /// Balance an operator tree from a list of children nodes.
+    /// An `outer` of `inner`s of literals, or a single `inner` or literal if `accept_outer` is false.
     ///
-    /// => moves operators from the left to the right hand of the tree.
-    fn build_right_handed_tree_from_node_list(&mut self, nodes: &mut [Node], op: Oper) {
-        if nodes.is_empty() {
-            return;
-        }
-        debug_assert!(nodes.len() >= 2, "Invalid call: {nodes:?}");
-        let mut i = nodes.len() - 1;
-        let mut new_right = mem::take(&mut nodes[i]);
-        i -= 1;
-        while i != 0 {
-            new_right = Node::Operator(Self::new(
-                op,
-                Box::new([mem::take(&mut nodes[i]), new_right]),
-            ));
-            i -= 1;
-        }
-        self.children[1] = new_right;
-        self.children[0] = mem::take(&mut nodes[i]);
-    }
-
-    /// We only apply the equivalence `| of &` -> `& of |` since it's what we need.
-    fn apply_distributive_equivalence(&mut self) {
-        debug_assert_eq!(self.char, Oper::Disjunction);
-        let new_or = |left, right| Op {
-            char: Oper::Disjunction,
-            children: Box::new([left, right]),
-        };
-
-        match &mut *self.children {
-            [
-                Node::Operator(Op {
-                    char: Oper::Conjunction,
-                    children: child_l,
-                }),
-                Node::Operator(Op {
-                    char: Oper::Conjunction,
-                    children: child_r,
-                }),
-            ] => {
-                self.char = Oper::Conjunction;
-
-                let mut new_operands = Box::new([
-                    new_or(child_l[0].clone(), child_r[0].clone()),
-                    new_or(child_l[1].clone(), mem::take(&mut child_r[0])),
-                    new_or(mem::take(&mut child_l[0]), child_r[1].clone()),
-                    new_or(mem::take(&mut child_l[1]), mem::take(&mut child_r[1])),
-                ]);
-                for op in new_operands.iter_mut() {
-                    op.apply_distributive_equivalence();
+    /// Literals are variables, values and their negations.
+    pub(crate) fn is_normal_form(&mut self, outer: Oper, inner: Oper, accept_outer: bool) -> bool {
+        let term = |form: &Form| matches!(form, Form::Literal | Form::Inner);
+        let form = self.fold(|node, mut children| match node {
+            Node::Operator(Op { char, .. }) if *char == outer => {
+                // Only the last operand of an `outer` may be another `outer`, as in a right handed chain
+                let last = children.next_back().unwrap();
+                if children.all(|child| term(&child)) && (term(&last) || last == Form::Outer) {
+                    Form::Outer
+                } else {
+                    Form::Invalid
                 }
-                let mut new_operands = new_operands.map(Node::Operator);
-                self.build_right_handed_tree_from_node_list(&mut new_operands, Oper::Conjunction);
             }
-            [
-                child_l,
-                Node::Operator(Op {
-                    char: Oper::Conjunction,
-                    children: nested_children,
-                }),
-            ] => {
-                self.char = Oper::Conjunction;
-                let mut new_ops = Box::new([
-                    new_or(child_l.clone(), mem::take(&mut nested_children[0])),
-                    new_or(mem::take(child_l), mem::take(&mut nested_children[1])),
-                ]);
-                for op in new_ops.iter_mut() {
-                    op.apply_distributive_equivalence();
+            Node::Operator(Op { char, .. }) if *char == inner => {
+                if children.all(|child| term(&child)) {
+                    Form::Inner
+                } else {
+                    Form::Invalid
                 }
-                self.build_right_handed_tree_from_node_list(
-                    &mut new_ops.map(Node::Operator),
-                    Oper::Conjunction,
-                );
             }
-            [
-                Node::Operator(Op {
-                    char: Oper::Conjunction,
-                    children: nested_children,
-                }),
-                child_r,
-            ] => {
-                self.char = Oper::Conjunction;
-                let mut new_ops = Box::new([
-                    new_or(mem::take(&mut nested_children[0]), child_r.clone()),
-                    new_or(mem::take(&mut nested_children[1]), mem::take(child_r)),
-                ]);
-                for op in new_ops.iter_mut() {
-                    op.apply_distributive_equivalence();
-                }
-                self.build_right_handed_tree_from_node_list(
-                    &mut new_ops.map(Node::Operator),
-                    Oper::Conjunction,
-                );
+            Node::Operator(_) => Form::Invalid,
+            Node::Neg(child) if matches!(**child, Node::Variable(_) | Node::Value(_)) => {
+                Form::Literal
             }
-            _ => (),
-        }
+            Node::Neg(_) => Form::Invalid,
+            Node::Variable(_) | Node::Value(_) => Form::Literal,
+        });
+        term(&form) || (accept_outer && form == Form::Outer)
     }
-}
 
-impl Node {
-    pub fn is_conjunctive_normal_form(&mut self, accept_conjunctions: bool) -> bool {
-        match self {
-            Node::Operator(Op { char, children }) => match (char, accept_conjunctions) {
-                (Oper::Conjunction, true) => {
-                    children[0].is_conjunctive_normal_form(false)
-                        && children[1].is_conjunctive_normal_form(true)
-                }
-                (Oper::Conjunction, false) => false,
-                (Oper::Disjunction, _) => {
-                    children[0].is_conjunctive_normal_form(false)
-                        && children[1].is_conjunctive_normal_form(false)
-                }
-                _ => false,
-            },
-            Node::Variable(_) | Node::Value(_) | Node::Neg(_) => true,
-        }
+    /// `self` MUST be in negation normal form
+    ///
+    /// `|` is distributed over `&`, the result is a single n-ary `&` of clauses,
+    /// each a single n-ary `|` of literals.
+    pub fn to_conjunctive_normal_form_mut(&mut self) {
+        self.distribute_mut(Oper::Conjunction, Oper::Disjunction);
     }
 
     /// `self` MUST be in negation normal form
-    fn to_conjunctive_normal_form_mut(&mut self) {
-        if let Node::Operator(op) = self {
-            op.children[0].to_conjunctive_normal_form_mut();
-            op.children[1].to_conjunctive_normal_form_mut();
-
-            if op.char == Oper::Disjunction {
-                op.apply_distributive_equivalence();
-                // Broken since I apply the equivalence on the nested ops ?
-                if op.char == Oper::Equivalence {
-                    return;
+    ///
+    /// `inner` is distributed over `outer`, the result is a single n-ary `outer` of terms,
+    /// each a single n-ary `inner` of literals.
+    pub(crate) fn distribute_mut(&mut self, outer: Oper, inner: Oper) {
+        // Long chains are distributed in one go instead of once per level
+        self.flatten_mut();
+        self.transform_bottom_up(|node| {
+            // The operands are already in normal form
+            let Node::Operator(Op { char, children }) = node else {
+                return;
+            };
+            let terms = if *char == outer {
+                children
+                    .drain(..)
+                    .flat_map(|child| child.into_operands(outer))
+                    .collect()
+            } else if *char == inner {
+                // One term for every way of picking a term in each operand
+                let mut terms: Vec<Vec<Node>> = vec![vec![]];
+                for child in children.drain(..) {
+                    let picks = child
+                        .into_operands(outer)
+                        .into_iter()
+                        .map(|pick| pick.into_operands(inner))
+                        .collect::<Vec<_>>();
+                    let mut next = Vec::with_capacity(terms.len() * picks.len());
+                    for mut term in terms {
+                        for (i, pick) in picks.iter().enumerate() {
+                            let mut new = if i + 1 == picks.len() {
+                                mem::take(&mut term)
+                            } else {
+                                term.clone()
+                            };
+                            new.extend(pick.iter().cloned());
+                            next.push(new);
+                        }
+                    }
+                    terms = next;
                 }
-            }
-            let mut operands = op.op_depth_first_search_chained();
-            op.build_right_handed_tree_from_node_list(&mut operands, op.char);
-        }
+                terms
+                    .into_iter()
+                    .map(|literals| Node::n_ary(inner, literals))
+                    .collect()
+            } else {
+                panic!(
+                    "'{}' is not allowed in negation normal form",
+                    char.ascii_char()
+                )
+            };
+            *node = Node::n_ary(outer, terms);
+        });
     }
 }
 
-fn cnf(formula: &str) -> Result<Node, MyError> {
+/// What a sub-formula is in [`Node::is_normal_form`]
+#[derive(PartialEq)]
+enum Form {
+    Literal,
+    Inner,
+    Outer,
+    Invalid,
+}
+
+fn nnf(formula: &str) -> Result<Node, MyError> {
     let mut tree = Node::parse(formula)?;
     tree.to_primitive_connectives_mut();
     tree.to_negation_normal_form_mut();
-    tree.to_conjunctive_normal_form_mut();
     Ok(tree)
 }
 
+/// Distributes the formula itself instead of printing [`clauses`]: the subject expects
+/// the operands in their order and the values, which a [`Cnf`] sorts, dedups and drops.
 pub fn conjunctive_normal_form(formula: &str) -> String {
-    cnf(formula)
-        .map(|n| n.to_string())
+    nnf(formula)
+        .map(|mut n| {
+            n.to_conjunctive_normal_form_mut();
+            n.to_string()
+        })
         .unwrap_or_else(|e| e.to_string())
 }
 
+/// Clauses of the conjunctive normal form of `formula`, without tautologies or subsumed ones
+///
+/// `|` is distributed over the clauses directly, no formula is built for them.
+pub fn clauses(formula: &str) -> Result<Cnf, MyError> {
+    let tree = nnf(formula)?;
+    let mut cnf = Cnf::new(tree.variables());
+    let indices = cnf
+        .variables()
+        .iter()
+        .enumerate()
+        .map(|(i, &var)| (var, i))
+        .collect::<HashMap<_, _>>();
+    // No clause is true, a single empty one is false
+    let clauses = tree.fold::<Vec<Clause>, _>(|node, mut children| match node {
+        Node::Value(value) => {
+            if *value {
+                vec![]
+            } else {
+                vec![Clause::default()]
+            }
+        }
+        Node::Variable(var) => vec![Clause::new([Literal::new(indices[var], true)])],
+        Node::Neg(_) => match children.next().unwrap()[..] {
+            [] => vec![Clause::default()],
+            [ref clause] if clause.is_empty() => vec![],
+            [ref clause] if clause.len() == 1 => vec![Clause::new([!clause.literals()[0]])],
+            _ => panic!("not in negation normal form: {node}"),
+        },
+        Node::Operator(Op { char, .. }) => match char {
+            Oper::Conjunction => children.flatten().collect(),
+            // One clause for every way of picking a clause in each operand
+            Oper::Disjunction => children.fold(vec![Clause::default()], |clauses, picks| {
+                clauses
+                    .iter()
+                    .flat_map(|clause| {
+                        picks.iter().map(move |pick| {
+                            Clause::new(clause.literals().iter().chain(pick.literals()).copied())
+                        })
+                    })
+                    .filter(|clause| !clause.is_tautology())
+                    .collect()
+            }),
+            _ => panic!(
+                "'{}' is not allowed in negation normal form",
+                char.ascii_char()
+            ),
+        },
+    });
+    for clause in clauses {
+        cnf.push(clause);
+    }
+    cnf.simplify();
+    Ok(cnf)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -205,6 +202,89 @@ mod tests {
         assert_eq!(conjunctive_normal_form("AB|!C!&"), "A!B!C!&&");
     }
 
+    #[test]
+    fn clauses() {
+        let cnf = super::clauses("AB&AC&|A|").unwrap();
+        assert_eq!(cnf.to_string(), "(1)");
+        let cnf = super::clauses("AB^").unwrap();
+        assert_eq!(cnf.to_string(), "(1 2) (-2 -1)");
+        assert!(super::clauses("AB").is_err());
+        assert_eq!(super::clauses("A0|1&").unwrap().to_string(), "(1)");
+        assert_eq!(super::clauses("A!A&").unwrap().to_string(), "(-1) (1)");
+        assert_eq!(super::clauses("A1|").unwrap().to_string(), "");
+
+        // Same clauses as the formula form
+        for _ in 0..100 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D']);
+            let formula = tree.to_string();
+            if formula.len() > 100 {
+                continue;
+            }
+            let mut expected =
+                Cnf::from_node(&Node::parse(conjunctive_normal_form(&formula)).unwrap());
+            expected.simplify();
+            assert_eq!(super::clauses(&formula).unwrap(), expected, "{formula}");
+        }
+    }
+
+    #[test]
+    fn named_variables() {
+        assert_eq!(
+            conjunctive_normal_form("[x1][x2]&[y]|"),
+            "[x1][y]|[x2][y]|&"
+        );
+    }
+
+    #[test]
+    fn n_ary() {
+        let mut tree = Node::parse("AB&C&DE&|").unwrap();
+        tree.flatten_mut();
+        tree.to_conjunctive_normal_form_mut();
+        assert_eq!(tree.to_string(), "AD|AE|BD|BE|CD|CE|&&&&&");
+        let Node::Operator(Op { children, .. }) = &tree else {
+            panic!("{tree:?}");
+        };
+        assert_eq!(children.len(), 6);
+        assert!(tree.is_conjunctive_normal_form(true));
+        assert!(
+            !Node::parse("AB&!")
+                .unwrap()
+                .is_conjunctive_normal_form(true)
+        );
+        assert!(
+            !Node::parse("AB|!C&")
+                .unwrap()
+                .is_conjunctive_normal_form(true)
+        );
+    }
+
+    #[test]
+    fn huge_normal_forms() {
+        use crate::bool_formula_ast::{huge_chain, huge_formula};
+
+        let mut clause = Node::parse(huge_chain('|')).unwrap();
+        assert!(clause.is_conjunctive_normal_form(false));
+        assert!(!clause.is_disjunctive_normal_form(true));
+        let mut term = Node::parse(huge_chain('&')).unwrap();
+        assert!(term.is_disjunctive_normal_form(false));
+        // Not a right handed chain
+        assert!(!term.is_conjunctive_normal_form(true));
+        assert!(
+            !Node::parse(huge_formula())
+                .unwrap()
+                .is_conjunctive_normal_form(true)
+        );
+    }
+
+    #[test]
+    fn huge_formula() {
+        let formula = crate::bool_formula_ast::huge_chain('|');
+        let mut tree = Node::parse(formula.as_str()).unwrap();
+        tree.to_conjunctive_normal_form_mut();
+        assert_eq!(tree.children().len(), 5_000_001);
+        assert!(tree.to_string().ends_with(&"|".repeat(5_000_000)));
+    }
+
     #[test]
     fn smoke_test_random() {
         for _ in 0..100 {
@@ -1,15 +1,31 @@
-use crate::ex04_truth_table::TruthTable;
+use crate::bool_formula_ast::{MyError, Node};
+use crate::cdcl::cdcl;
+use crate::clause::Cnf;
+use crate::tseitin::Polarity;
 
+/// Runs [`cdcl`] on the Tseitin encoding of `formula`.
+pub fn checked_sat(formula: &str) -> Result<bool, MyError> {
+    let tree = Node::parse(formula)?;
+    let encoding = tree.tseitin(Polarity::Needed);
+    Ok(cdcl(&Cnf::from_node(encoding.cnf())).is_some())
+}
+
+/// # Panics
+///
+/// If `formula` is invalid, see [`checked_sat`].
 pub fn sat(formula: &str) -> bool {
-    TruthTable::compute(formula)
-        .unwrap()
-        .entries()
-        .any(|(_, r)| r)
+    checked_sat(formula).unwrap()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::bit_parallel::satisfiable;
+
+    /// Tries every assignment, 64 at a time
+    fn truth_table_sat(formula: &str) -> bool {
+        satisfiable(&Node::parse(formula).unwrap())
+    }
 
     #[test]
     fn subject() {
@@ -18,4 +34,38 @@ mod tests {
         assert!(!sat("AA!&"));
         assert!(!sat("AA^"));
     }
+
+    #[test]
+    fn errors() {
+        assert!(matches!(
+            checked_sat("AB"),
+            Err(MyError::TrailingOperands(_))
+        ));
+        assert!(matches!(
+            checked_sat("A&"),
+            Err(MyError::MissingOperand('&', _))
+        ));
+        assert!(checked_sat("0").is_ok_and(|sat| !sat));
+        assert!(checked_sat("1").is_ok_and(|sat| sat));
+    }
+
+    #[test]
+    fn many_variables() {
+        // x0 > x1 > ... > x999, x0 and !x999
+        let chain = (1..1000)
+            .map(|i| format!("[x{}]![x{i}]|", i - 1))
+            .collect::<String>();
+        let chain = format!("{chain}{}", "&".repeat(998));
+        assert!(sat(&format!("{chain}[x0]&")));
+        assert!(!sat(&format!("{chain}[x0]&[x999]!&")));
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..100 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            let formula = tree.to_string();
+            assert_eq!(sat(&formula), truth_table_sat(&formula), "{formula}");
+        }
+    }
 }
@@ -2,7 +2,9 @@ use std::cmp::Ordering;
 
 use thiserror::Error;
 
-use crate::bool_formula_ast::{MyError, Node};
+use crate::bool_formula_ast::{MyError, Node, Op, Oper};
+use crate::diagnostic::{Span, Spanned};
+use crate::variable::Var;
 
 #[derive(Error, Debug)]
 pub enum SetFormulaError {
@@ -11,78 +13,94 @@ pub enum SetFormulaError {
     #[error("missing value for operator: {0}")]
     MissingValue(char),
     #[error("missing set: {0}")]
-    MissingSet(char),
+    MissingSet(Var),
     #[error("more than one result")]
     TooManyValues(),
     #[error("parsing error: {0}")]
     ParsingError(#[from] MyError),
 }
 
-pub fn checked_eval_set(formula: &str, sets: &[&[i32]]) -> Result<Vec<i32>, SetFormulaError> {
-    let mut tree = Node::parse(formula)?;
-    tree.to_primitive_connectives_mut();
-    tree.to_negation_normal_form_mut();
-    let formula = tree.to_string();
-
-    let mut sets: Vec<Vec<i32>> = sets.iter().map(|s| s.to_vec()).collect();
-    sets.iter_mut().for_each(|s| s.sort());
+impl Spanned for SetFormulaError {
+    /// Only parsing errors can be located, the others are found on the normalized formula.
+    fn span(&self) -> Option<Span> {
+        match self {
+            Self::ParsingError(e) => e.span(),
+            _ => None,
+        }
+    }
+}
 
-    let mut val_stack = Vec::new();
+/// Evaluates a formula in NNF, `set_of` gives the set each variable stands for.
+fn eval_set_tree<'a, F>(tree: &Node, mut set_of: F) -> Result<Vec<i32>, SetFormulaError>
+where
+    F: FnMut(Var) -> Option<&'a [i32]>,
+{
+    let mut val_stack: Vec<Vec<i32>> = Vec::new();
 
-    for &val in formula.as_bytes() {
-        match val {
-            b'A'..=b'Z' => {
-                let set = sets
-                    .get((val - b'A') as usize)
-                    .ok_or(SetFormulaError::MissingSet(val as char))?
-                    .clone();
+    for node in tree.post_order() {
+        match node {
+            Node::Variable(var) => {
+                let mut set = set_of(*var)
+                    .ok_or(SetFormulaError::MissingSet(*var))?
+                    .to_vec();
+                set.sort();
                 val_stack.push(set)
             }
-            b'!' => {
+            Node::Neg(_) => {
                 let a = val_stack
                     .last_mut()
                     .ok_or(SetFormulaError::MissingValue('!'))?;
                 a.clear();
             }
-            b'&' | b'|' => {
-                let b = val_stack
-                    .pop()
-                    .ok_or(SetFormulaError::MissingValue(val as char))?;
-                let mut a = val_stack
-                    .pop()
-                    .ok_or(SetFormulaError::MissingValue(val as char))?;
-                match val {
-                    // Conjunction ∧
-                    b'&' => {
-                        let mut new = vec![];
-                        let mut i = 0;
-                        let mut j = 0;
-                        while i < a.len() && j < b.len() {
-                            match a[i].cmp(&b[j]) {
-                                Ordering::Equal => {
-                                    new.push(a[i]);
-                                    i += 1;
-                                    j += 1;
-                                }
-                                Ordering::Less => {
-                                    i += 1;
-                                }
-                                Ordering::Greater => {
-                                    j += 1;
+            Node::Operator(Op {
+                char: op @ (Oper::Conjunction | Oper::Disjunction),
+                children,
+            }) => {
+                let first = val_stack
+                    .len()
+                    .checked_sub(children.len())
+                    .ok_or(SetFormulaError::MissingValue(op.ascii_char()))?;
+                let mut operands = val_stack.drain(first..);
+                let mut a = operands.next().unwrap();
+                for b in operands {
+                    match op {
+                        // Conjunction ∧
+                        Oper::Conjunction => {
+                            let mut new = vec![];
+                            let mut i = 0;
+                            let mut j = 0;
+                            while i < a.len() && j < b.len() {
+                                match a[i].cmp(&b[j]) {
+                                    Ordering::Equal => {
+                                        new.push(a[i]);
+                                        i += 1;
+                                        j += 1;
+                                    }
+                                    Ordering::Less => {
+                                        i += 1;
+                                    }
+                                    Ordering::Greater => {
+                                        j += 1;
+                                    }
                                 }
                             }
+                            a = new;
                         }
-                        a = new;
-                    }
-                    // disjunction ∨
-                    b'|' => {
-                        a.extend(b.iter());
+                        // disjunction ∨
+                        Oper::Disjunction => {
+                            a.extend(b.iter());
+                        }
+                        _ => unreachable!(),
                     }
-                    _ => unreachable!(),
                 }
                 val_stack.push(a);
             }
-            _ => return Err(SetFormulaError::InvalidChar(val as char)),
+            Node::Operator(Op { char: op, .. }) => {
+                return Err(SetFormulaError::InvalidChar(op.ascii_char()));
+            }
+            Node::Value(val) => {
+                return Err(SetFormulaError::InvalidChar(if *val { '1' } else { '0' }));
+            }
         }
     }
     if val_stack.len() == 1 {
@@ -92,6 +110,36 @@ pub fn checked_eval_set(formula: &str, sets: &[&[i32]]) -> Result<Vec<i32>, SetF
     }
 }
 
+/// Parses `formula` and brings it to NNF, where only `&`, `|` and negated variables remain.
+fn parse_set_formula(formula: &str) -> Result<Node, SetFormulaError> {
+    let mut tree = Node::parse(formula)?;
+    tree.to_primitive_connectives_mut();
+    tree.to_negation_normal_form_mut();
+    Ok(tree)
+}
+
+/// `sets[0]` is the value of `A`, `sets[1]` the value of `B`, ...
+pub fn checked_eval_set(formula: &str, sets: &[&[i32]]) -> Result<Vec<i32>, SetFormulaError> {
+    let tree = parse_set_formula(formula)?;
+    eval_set_tree(&tree, |var| {
+        let letter = var.as_letter()?;
+        sets.get((letter as u8 - b'A') as usize).copied()
+    })
+}
+
+/// Same as [`checked_eval_set`] for formulas with named variables, e.g. `[x1][x2]&`.
+pub fn checked_eval_named_sets(
+    formula: &str,
+    sets: &[(&str, &[i32])],
+) -> Result<Vec<i32>, SetFormulaError> {
+    let tree = parse_set_formula(formula)?;
+    eval_set_tree(&tree, |var| {
+        sets.iter()
+            .find(|(name, _)| *name == var.name())
+            .map(|(_, set)| *set)
+    })
+}
+
 pub fn eval_set(formula: &str, sets: &[&[i32]]) -> Vec<i32> {
     checked_eval_set(formula, sets).unwrap()
 }
@@ -108,5 +156,22 @@ mod tests {
             vec![0, 1, 2, 3, 4, 5]
         );
         assert_eq!(eval_set("A!", &[&[0, 1, 2]]), vec![]);
+        assert!(matches!(
+            checked_eval_set("AC&", &[&[0], &[1]]),
+            Err(SetFormulaError::MissingSet(v)) if v == Var::from('C')
+        ));
+    }
+
+    #[test]
+    fn test_eval_named_sets() {
+        assert_eq!(
+            checked_eval_named_sets("[x1][x2]&", &[("x1", &[2, 1, 0]), ("x2", &[0, 3, 1])])
+                .unwrap(),
+            vec![0, 1]
+        );
+        assert!(matches!(
+            checked_eval_named_sets("[x1]A|", &[("x1", &[0])]),
+            Err(SetFormulaError::MissingSet(v)) if v == Var::from('A')
+        ));
     }
 }
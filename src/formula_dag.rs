@@ -0,0 +1,432 @@
+//! Arena allocated formulas with structural sharing (hash-consing).
+//!
+//! Every distinct sub-formula is stored once in a [`Dag`] and referenced by its [`NodeId`],
+//! so the rewrites of the normal form pipeline (`^`, `=` and distributivity duplicate operands)
+//! don't copy anything.
+use std::collections::HashMap;
+use std::hash::Hash;
+
+use crate::bool_formula_ast::{Node, Op, Oper};
+use crate::variable::Var;
+
+/// Index of a node in its [`Dag`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Position in its [`Dag`], nodes come after their children
+    pub fn index(&self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DagNode {
+    Value(bool),
+    Variable(Var),
+    Neg(NodeId),
+    Operator(Oper, [NodeId; 2]),
+}
+
+impl DagNode {
+    pub fn children(&self) -> &[NodeId] {
+        match self {
+            Self::Neg(child) => std::slice::from_ref(child),
+            Self::Operator(_, children) => children,
+            Self::Value(_) | Self::Variable(_) => &[],
+        }
+    }
+}
+
+/// Arena of unique formula nodes.
+///
+/// Nodes are immutable, transformations build new nodes in the same arena
+/// and return the id of the new root.
+#[derive(Debug, Default)]
+pub struct Dag {
+    nodes: Vec<DagNode>,
+    ids: HashMap<DagNode, NodeId>,
+}
+
+impl Dag {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Number of distinct nodes stored.
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn get(&self, id: NodeId) -> DagNode {
+        self.nodes[id.0 as usize]
+    }
+
+    /// Ids of the stored nodes, children before their parents
+    pub fn node_ids(&self) -> impl Iterator<Item = NodeId> + use<> {
+        (0..self.nodes.len() as u32).map(NodeId)
+    }
+
+    /// Returns the id of `node`, storing it first if it's new.
+    pub fn intern(&mut self, node: DagNode) -> NodeId {
+        if let Some(&id) = self.ids.get(&node) {
+            return id;
+        }
+        let id = NodeId(self.nodes.len().try_into().expect("too many nodes"));
+        self.nodes.push(node);
+        self.ids.insert(node, id);
+        id
+    }
+
+    pub fn value(&mut self, value: bool) -> NodeId {
+        self.intern(DagNode::Value(value))
+    }
+
+    pub fn variable(&mut self, var: Var) -> NodeId {
+        self.intern(DagNode::Variable(var))
+    }
+
+    /// Negates `id`, removing double negations like [`Node::neg`].
+    pub fn neg(&mut self, id: NodeId) -> NodeId {
+        match self.get(id) {
+            DagNode::Neg(child) => child,
+            _ => self.intern(DagNode::Neg(id)),
+        }
+    }
+
+    pub fn operator(&mut self, op: Oper, left: NodeId, right: NodeId) -> NodeId {
+        self.intern(DagNode::Operator(op, [left, right]))
+    }
+
+    /// Adds the tree `node` to the arena, sharing its identical sub-formulas.
+    pub fn insert(&mut self, node: &Node) -> NodeId {
+        node.fold(|node, mut children| match node {
+            Node::Value(val) => self.value(*val),
+            Node::Variable(var) => self.variable(*var),
+            Node::Neg(_) => self.neg(children.next().unwrap()),
+            Node::Operator(Op { char, .. }) => {
+                let operands = children.collect::<Vec<_>>();
+                self.chain(*char, &operands)
+            }
+        })
+    }
+
+    /// Id of the tree `node` if the arena already holds it, without inserting anything.
+    pub fn find(&self, node: &Node) -> Option<NodeId> {
+        let find = |node: DagNode| self.ids.get(&node).copied();
+        node.fold(|node, mut children| match node {
+            Node::Value(val) => find(DagNode::Value(*val)),
+            Node::Variable(var) => find(DagNode::Variable(*var)),
+            Node::Neg(_) => {
+                let child = children.next().unwrap()?;
+                match self.get(child) {
+                    DagNode::Neg(grand_child) => Some(grand_child),
+                    _ => find(DagNode::Neg(child)),
+                }
+            }
+            Node::Operator(Op { char, .. }) => {
+                // Same right handed chain as `insert`
+                let operands = children.collect::<Option<Vec<_>>>()?;
+                let (&last, rest) = operands.split_last().unwrap();
+                rest.iter().rev().try_fold(last, |acc, &operand| {
+                    find(DagNode::Operator(*char, [operand, acc]))
+                })
+            }
+        })
+    }
+
+    /// Expands `id` back into a tree.
+    ///
+    /// Shared sub-formulas are duplicated, the tree can be exponentially bigger than the DAG.
+    pub fn to_node(&self, id: NodeId) -> Node {
+        let mut stack = vec![(id, false)];
+        let mut done: Vec<Node> = Vec::new();
+
+        while let Some((id, expanded)) = stack.pop() {
+            let node = self.get(id);
+            if !expanded && !node.children().is_empty() {
+                stack.push((id, true));
+                stack.extend(node.children().iter().rev().map(|&c| (c, false)));
+                continue;
+            }
+            let built = match node {
+                DagNode::Value(val) => Node::Value(val),
+                DagNode::Variable(var) => Node::Variable(var),
+                DagNode::Neg(_) => Node::Neg(Box::new(done.pop().unwrap())),
+                DagNode::Operator(op, _) => {
+                    let right = done.pop().unwrap();
+                    let left = done.pop().unwrap();
+                    Node::Operator(Op::new(op, vec![left, right]))
+                }
+            };
+            done.push(built);
+        }
+        done.pop().unwrap()
+    }
+
+    /// Memoized post-order traversal of the keys reachable from `root`.
+    ///
+    /// `children` lists the keys a key depends on, `build` computes the node of a key
+    /// once all its dependencies are in the memo.
+    fn rewrite<K, C, B>(&mut self, root: K, children: C, mut build: B) -> NodeId
+    where
+        K: Copy + Eq + Hash,
+        C: Fn(&Self, K, &mut Vec<K>),
+        B: FnMut(&mut Self, K, &HashMap<K, NodeId>) -> NodeId,
+    {
+        let mut memo: HashMap<K, NodeId> = HashMap::new();
+        let mut stack = vec![(root, false)];
+        let mut deps = Vec::new();
+
+        while let Some((key, expanded)) = stack.pop() {
+            if memo.contains_key(&key) {
+                continue;
+            }
+            if expanded {
+                let id = build(self, key, &memo);
+                memo.insert(key, id);
+            } else {
+                stack.push((key, true));
+                children(self, key, &mut deps);
+                stack.extend(
+                    deps.drain(..)
+                        .filter(|k| !memo.contains_key(k))
+                        .map(|k| (k, false)),
+                );
+            }
+        }
+        memo[&root]
+    }
+
+    /// Same as [`Node::to_primitive_connectives_mut`]: only `!`, `&` and `|` remain.
+    pub fn to_primitive_connectives(&mut self, root: NodeId) -> NodeId {
+        self.rewrite(
+            root,
+            |dag, id, deps| deps.extend(dag.get(id).children()),
+            |dag, id, memo| match dag.get(id) {
+                DagNode::Value(_) | DagNode::Variable(_) => id,
+                DagNode::Neg(child) => dag.neg(memo[&child]),
+                DagNode::Operator(op, [left, right]) => {
+                    let (l, r) = (memo[&left], memo[&right]);
+                    match op {
+                        Oper::Conjunction | Oper::Disjunction => dag.operator(op, l, r),
+                        Oper::ExclusiveDisjunction => {
+                            let or = dag.operator(Oper::Disjunction, l, r);
+                            let and = dag.operator(Oper::Conjunction, l, r);
+                            let nand = dag.neg(and);
+                            dag.operator(Oper::Conjunction, or, nand)
+                        }
+                        Oper::Equivalence => {
+                            let (not_l, not_r) = (dag.neg(l), dag.neg(r));
+                            let l_implies_r = dag.operator(Oper::Disjunction, not_l, r);
+                            let r_implies_l = dag.operator(Oper::Disjunction, not_r, l);
+                            dag.operator(Oper::Conjunction, l_implies_r, r_implies_l)
+                        }
+                        Oper::MaterialCondition => {
+                            let not_l = dag.neg(l);
+                            dag.operator(Oper::Disjunction, not_l, r)
+                        }
+                    }
+                }
+            },
+        )
+    }
+
+    /// `root` MUST be in primitive connectives
+    ///
+    /// Same as [`Node::to_negation_normal_form_mut`], negations are pushed down with De Morgan's laws.
+    pub fn to_negation_normal_form(&mut self, root: NodeId) -> NodeId {
+        // Keys are a node and whether it appears under an odd number of negations
+        self.rewrite(
+            (root, false),
+            |dag, (id, negated), deps| match dag.get(id) {
+                DagNode::Neg(child) => deps.push((child, !negated)),
+                node => deps.extend(node.children().iter().map(|&c| (c, negated))),
+            },
+            |dag, (id, negated), memo| match dag.get(id) {
+                DagNode::Value(val) => dag.value(val != negated),
+                DagNode::Variable(_) if negated => dag.neg(id),
+                DagNode::Variable(_) => id,
+                DagNode::Neg(child) => memo[&(child, !negated)],
+                DagNode::Operator(op, [left, right]) => {
+                    let op = match (op, negated) {
+                        (op, false) => op,
+                        (Oper::Conjunction, true) => Oper::Disjunction,
+                        (Oper::Disjunction, true) => Oper::Conjunction,
+                        (op, true) => panic!("'{}' is not a primitive connective", op.ascii_char()),
+                    };
+                    dag.operator(op, memo[&(left, negated)], memo[&(right, negated)])
+                }
+            },
+        )
+    }
+
+    /// Operands of the chain of `op` rooted at `id`, from left to right.
+    fn chained_operands(&self, id: NodeId, op: Oper) -> Vec<NodeId> {
+        let mut operands = Vec::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            match self.get(id) {
+                DagNode::Operator(o, [left, right]) if o == op => {
+                    stack.push(right);
+                    stack.push(left);
+                }
+                _ => operands.push(id),
+            }
+        }
+        operands
+    }
+
+    /// Right handed chain of `op` over `operands`, the binary form of an n-ary [`Op`].
+    pub fn chain(&mut self, op: Oper, operands: &[NodeId]) -> NodeId {
+        let (&last, rest) = operands.split_last().expect("empty chain");
+        rest.iter()
+            .rev()
+            .fold(last, |acc, &operand| self.operator(op, operand, acc))
+    }
+
+    /// `root` MUST be in negation normal form
+    ///
+    /// Same as ex06's conjunctive normal form: `|` is distributed over `&`,
+    /// and every chain of operators is right handed.
+    pub fn to_conjunctive_normal_form(&mut self, root: NodeId) -> NodeId {
+        self.rewrite(
+            root,
+            |dag, id, deps| {
+                if let DagNode::Operator(_, children) = dag.get(id) {
+                    deps.extend(children);
+                }
+            },
+            |dag, id, memo| match dag.get(id) {
+                DagNode::Operator(Oper::Conjunction, [left, right]) => {
+                    let mut clauses = dag.chained_operands(memo[&left], Oper::Conjunction);
+                    clauses.extend(dag.chained_operands(memo[&right], Oper::Conjunction));
+                    dag.chain(Oper::Conjunction, &clauses)
+                }
+                DagNode::Operator(Oper::Disjunction, [left, right]) => {
+                    let left_clauses = dag.chained_operands(memo[&left], Oper::Conjunction);
+                    let right_clauses = dag.chained_operands(memo[&right], Oper::Conjunction);
+                    let mut clauses = Vec::with_capacity(left_clauses.len() * right_clauses.len());
+                    for &l in left_clauses.iter() {
+                        for &r in right_clauses.iter() {
+                            let mut literals = dag.chained_operands(l, Oper::Disjunction);
+                            literals.extend(dag.chained_operands(r, Oper::Disjunction));
+                            clauses.push(dag.chain(Oper::Disjunction, &literals));
+                        }
+                    }
+                    dag.chain(Oper::Conjunction, &clauses)
+                }
+                DagNode::Operator(op, _) => {
+                    panic!(
+                        "'{}' is not allowed in negation normal form",
+                        op.ascii_char()
+                    )
+                }
+                DagNode::Value(_) | DagNode::Variable(_) | DagNode::Neg(_) => id,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ex04_truth_table::TruthTable;
+
+    #[test]
+    fn hash_consing() {
+        let mut dag = Dag::new();
+        let root = dag.insert(&Node::parse("AB&AB&|").unwrap());
+        // A, B, AB& and the root
+        assert_eq!(dag.len(), 4);
+        assert_eq!(
+            dag.insert(&Node::parse("AB&").unwrap()),
+            dag.chained_operands(root, Oper::Disjunction)[0]
+        );
+        assert_eq!(dag.to_node(root).to_string(), "AB&AB&|");
+        let negated = dag.neg(root);
+        assert_eq!(dag.neg(negated), root);
+
+        // n-ary operators are stored as right handed chains
+        let mut n_ary = Node::parse("AB&C&").unwrap();
+        n_ary.flatten_mut();
+        let chain = dag.insert(&Node::parse("ABC&&").unwrap());
+        assert_eq!(dag.find(&n_ary), Some(chain));
+        assert_eq!(dag.insert(&n_ary), chain);
+    }
+
+    #[test]
+    fn round_trip_random() {
+        let mut dag = Dag::new();
+        for _ in 0..100 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            let tree = Node::parse(tree.to_string()).unwrap();
+            let id = dag.insert(&tree);
+            assert_eq!(dag.to_node(id), tree);
+        }
+    }
+
+    #[test]
+    fn shared_rewrites_stay_small() {
+        // Every `^` and `=` duplicates both operands, as a tree this is exponential
+        let mut formula = String::from("A");
+        for i in 0..40 {
+            formula.push(char::from(b'B' + i % 20));
+            formula.push(if i % 2 == 0 { '^' } else { '=' });
+        }
+        let mut dag = Dag::new();
+        let root = dag.insert(&Node::parse(formula).unwrap());
+        let primitive = dag.to_primitive_connectives(root);
+        dag.to_negation_normal_form(primitive);
+        assert!(dag.len() < 1000, "{} nodes", dag.len());
+    }
+
+    #[test]
+    fn normal_forms_match_tree_pipeline() {
+        assert_eq!(dag_cnf("AB&!"), "A!B!|");
+        assert_eq!(dag_cnf("AB|!"), "A!B!&");
+        assert_eq!(dag_cnf("AB|C&"), "AB|C&");
+        assert_eq!(dag_cnf("AB|C|D|"), "ABCD|||");
+        assert_eq!(dag_cnf("AB&C&D&"), "ABCD&&&");
+        assert_eq!(dag_cnf("AB&!C!|"), "A!B!C!||");
+        assert_eq!(dag_cnf("AB|!C!&"), "A!B!C!&&");
+
+        for _ in 0..100 {
+            let mut tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            tree.to_primitive_connectives_mut();
+            tree.to_negation_normal_form_mut();
+            let formula = tree.to_string();
+            if formula.len() > 100 {
+                // The number of clauses is still exponential
+                continue;
+            }
+            let cnf = dag_cnf(&formula);
+            println!("{formula} -> {cnf}");
+            assert!(
+                Node::parse(cnf.as_str())
+                    .unwrap()
+                    .is_conjunctive_normal_form(true)
+            );
+            let truth_table = TruthTable::compute(&formula).unwrap();
+            assert_eq!(
+                TruthTable::compute_with_given_vars(&cnf, truth_table.variables().to_vec())
+                    .unwrap()
+                    .to_string(),
+                truth_table.to_string()
+            );
+        }
+    }
+
+    fn dag_cnf(formula: &str) -> String {
+        let mut dag = Dag::new();
+        let root = dag.insert(&Node::parse(formula).unwrap());
+        let root = dag.to_primitive_connectives(root);
+        let root = dag.to_negation_normal_form(root);
+        let root = dag.to_conjunctive_normal_form(root);
+        dag.to_node(root).to_string()
+    }
+}
@@ -0,0 +1,316 @@
+//! Graphviz DOT export of formulas, render with e.g. `dot -Tsvg formula.dot`.
+//!
+//! Negations are not drawn as nodes: the edge to a negated operand is dashed and ends with a circle.
+//! A negated root hangs from a point node.
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::bool_formula_ast::{Node, Op, Oper};
+use crate::formula_dag::{Dag, DagNode, NodeId};
+
+const HIGHLIGHT: &str = "color=red, fontcolor=red, penwidth=2";
+
+#[derive(Debug, Clone, Copy)]
+pub struct DotOptions<'a> {
+    /// Name of the `digraph`, any string
+    pub name: &'a str,
+    /// Every occurrence of this sub-formula is drawn in red
+    pub highlight: Option<&'a Node>,
+}
+
+impl Default for DotOptions<'_> {
+    fn default() -> Self {
+        Self {
+            name: "formula",
+            highlight: None,
+        }
+    }
+}
+
+/// A node of the drawing, along with the edges to its operands.
+struct Drawn {
+    label: String,
+    is_operator: bool,
+    highlighted: bool,
+    /// Index of the operand and whether it's negated
+    operands: Vec<(usize, bool)>,
+}
+
+fn label(op: Oper) -> String {
+    op.utf8_char().to_string()
+}
+
+/// `s` as a quoted DOT ID
+fn quoted(s: &str) -> String {
+    format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Writes the `digraph`, `root` is the index of the root and whether it's negated.
+fn write_dot(name: &str, drawn: &[Drawn], root: (usize, bool)) -> String {
+    let mut dot = String::new();
+    let edge_attributes = |negated: bool, highlighted: bool| {
+        let mut attributes = Vec::new();
+        if negated {
+            attributes.push("style=dashed, arrowhead=odot");
+        }
+        if highlighted {
+            attributes.push(HIGHLIGHT);
+        }
+        match attributes.is_empty() {
+            true => String::new(),
+            false => format!(" [{}]", attributes.join(", ")),
+        }
+    };
+
+    writeln!(dot, "digraph {} {{", quoted(name)).unwrap();
+    writeln!(dot, "    ordering=out;").unwrap();
+    for (i, node) in drawn.iter().enumerate() {
+        let shape = if node.is_operator { "circle" } else { "box" };
+        write!(
+            dot,
+            "    n{i} [label={}, shape={shape}",
+            quoted(&node.label)
+        )
+        .unwrap();
+        if node.highlighted {
+            write!(dot, ", {HIGHLIGHT}").unwrap();
+        }
+        writeln!(dot, "];").unwrap();
+    }
+    for (i, node) in drawn.iter().enumerate() {
+        for &(operand, negated) in node.operands.iter() {
+            let attributes = edge_attributes(negated, drawn[operand].highlighted);
+            writeln!(dot, "    n{i} -> n{operand}{attributes};").unwrap();
+        }
+    }
+    let (root, negated) = root;
+    if negated {
+        writeln!(dot, "    root [shape=point];").unwrap();
+        let attributes = edge_attributes(true, drawn[root].highlighted);
+        writeln!(dot, "    root -> n{root}{attributes};").unwrap();
+    }
+    writeln!(dot, "}}").unwrap();
+    dot
+}
+
+impl Node {
+    /// Draws the tree, identical sub-formulas are drawn as many times as they appear.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        /// Sub-formula as seen by its parent
+        struct Sub {
+            /// Drawn node, its negations are folded into the edge
+            index: usize,
+            negated: bool,
+            /// Index of the first drawn node of the sub-formula, all the following ones up to `index` belong to it
+            first: usize,
+            id: NodeId,
+        }
+
+        // Identical sub-formulas have the same id in the DAG
+        let mut dag = Dag::new();
+        let highlight = options.highlight.map(|h| dag.insert(h));
+        let mut drawn: Vec<Drawn> = Vec::new();
+        let mut highlighted_ranges = Vec::new();
+
+        let root = self.fold(|node, mut operands: std::vec::Drain<'_, Sub>| {
+            let sub = match node {
+                Node::Neg(_) => {
+                    let child = operands.next().unwrap();
+                    Sub {
+                        negated: !child.negated,
+                        id: dag.neg(child.id),
+                        ..child
+                    }
+                }
+                Node::Value(_) | Node::Variable(_) | Node::Operator(_) => {
+                    let operands = operands.collect::<Vec<_>>();
+                    let (label, id) = match node {
+                        Node::Value(val) => ((*val as u8).to_string(), dag.value(*val)),
+                        Node::Variable(var) => (var.to_string(), dag.variable(*var)),
+                        Node::Operator(Op { char, .. }) => {
+                            let ids = operands.iter().map(|o| o.id).collect::<Vec<_>>();
+                            let id = dag.chain(*char, &ids);
+                            (label(*char), id)
+                        }
+                        Node::Neg(_) => unreachable!(),
+                    };
+                    drawn.push(Drawn {
+                        label,
+                        is_operator: !operands.is_empty(),
+                        highlighted: false,
+                        operands: operands.iter().map(|o| (o.index, o.negated)).collect(),
+                    });
+                    let index = drawn.len() - 1;
+                    Sub {
+                        index,
+                        negated: false,
+                        first: operands.first().map_or(index, |o| o.first),
+                        id,
+                    }
+                }
+            };
+            if Some(sub.id) == highlight {
+                highlighted_ranges.push(sub.first..=sub.index);
+            }
+            sub
+        });
+        for range in highlighted_ranges {
+            drawn[range].iter_mut().for_each(|d| d.highlighted = true);
+        }
+
+        write_dot(options.name, &drawn, (root.index, root.negated))
+    }
+}
+
+impl Dag {
+    /// Removes the negation of `id` if any.
+    fn strip_neg(&self, id: NodeId) -> (NodeId, bool) {
+        match self.get(id) {
+            DagNode::Neg(child) => (child, true),
+            _ => (id, false),
+        }
+    }
+
+    /// Draws the formula rooted at `root`, shared sub-formulas are drawn once.
+    pub fn to_dot(&self, root: NodeId, options: &DotOptions) -> String {
+        let mut indices = HashMap::new();
+        let mut drawn: Vec<Drawn> = Vec::new();
+        let mut operand_ids = Vec::new();
+        let (root, root_negated) = self.strip_neg(root);
+
+        // Assign an index to every reachable node that isn't a negation
+        let mut stack = vec![root];
+        while let Some(id) = stack.pop() {
+            if indices.contains_key(&id) {
+                continue;
+            }
+            indices.insert(id, drawn.len());
+            let (label, operands) = match self.get(id) {
+                DagNode::Value(val) => ((val as u8).to_string(), vec![]),
+                DagNode::Variable(var) => (var.to_string(), vec![]),
+                DagNode::Operator(op, children) => (label(op), children.to_vec()),
+                DagNode::Neg(_) => unreachable!("negations are stripped"),
+            };
+            drawn.push(Drawn {
+                label,
+                is_operator: !operands.is_empty(),
+                highlighted: false,
+                // Resolved once every node has an index
+                operands: Vec::new(),
+            });
+            stack.extend(operands.iter().rev().map(|&o| self.strip_neg(o).0));
+            operand_ids.push(operands);
+        }
+        for (node, operands) in drawn.iter_mut().zip(operand_ids) {
+            node.operands = operands
+                .into_iter()
+                .map(|o| {
+                    let (id, negated) = self.strip_neg(o);
+                    (indices[&id], negated)
+                })
+                .collect();
+        }
+
+        // Highlight everything reachable from the highlighted sub-formula
+        let highlight = options.highlight.and_then(|h| self.find(h));
+        let mut stack: Vec<usize> = highlight
+            .and_then(|h| indices.get(&self.strip_neg(h).0))
+            .into_iter()
+            .copied()
+            .collect();
+        while let Some(i) = stack.pop() {
+            if !drawn[i].highlighted {
+                drawn[i].highlighted = true;
+                stack.extend(drawn[i].operands.iter().map(|&(o, _)| o));
+            }
+        }
+
+        write_dot(options.name, &drawn, (indices[&root], root_negated))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tree() {
+        let tree = Node::parse("AB!&A|!").unwrap();
+        assert_eq!(
+            tree.to_dot(&DotOptions::default()),
+            r#"digraph "formula" {
+    ordering=out;
+    n0 [label="A", shape=box];
+    n1 [label="B", shape=box];
+    n2 [label="∧", shape=circle];
+    n3 [label="A", shape=box];
+    n4 [label="∨", shape=circle];
+    n2 -> n0;
+    n2 -> n1 [style=dashed, arrowhead=odot];
+    n4 -> n2;
+    n4 -> n3;
+    root [shape=point];
+    root -> n4 [style=dashed, arrowhead=odot];
+}
+"#
+        );
+    }
+
+    #[test]
+    fn tree_highlight() {
+        let tree = Node::parse("AB!&AB!&|").unwrap();
+        let highlight = Node::parse("AB!&").unwrap();
+        let dot = tree.to_dot(&DotOptions {
+            name: "cnf",
+            highlight: Some(&highlight),
+        });
+        assert!(dot.starts_with("digraph \"cnf\" {"));
+        // Both occurrences, but not the root
+        assert_eq!(dot.matches("shape=box, color=red").count(), 4);
+        assert_eq!(dot.matches("shape=circle, color=red").count(), 2);
+        assert!(dot.contains("n6 [label=\"∨\", shape=circle];"));
+        assert!(dot.contains("n2 -> n1 [style=dashed, arrowhead=odot, color=red"));
+        // Edges carry the negation of their operand, so they belong to it
+        assert!(dot.contains("n6 -> n2 [color=red"));
+    }
+
+    #[test]
+    fn dag() {
+        let mut dag = Dag::new();
+        let root = dag.insert(&Node::parse("AB!&AB!&|!").unwrap());
+        let highlight = Node::parse("B!").unwrap();
+        assert_eq!(
+            dag.to_dot(
+                root,
+                &DotOptions {
+                    highlight: Some(&highlight),
+                    ..Default::default()
+                }
+            ),
+            r#"digraph "formula" {
+    ordering=out;
+    n0 [label="∨", shape=circle];
+    n1 [label="∧", shape=circle];
+    n2 [label="A", shape=box];
+    n3 [label="B", shape=box, color=red, fontcolor=red, penwidth=2];
+    n0 -> n1;
+    n0 -> n1;
+    n1 -> n2;
+    n1 -> n3 [style=dashed, arrowhead=odot, color=red, fontcolor=red, penwidth=2];
+    root [shape=point];
+    root -> n0 [style=dashed, arrowhead=odot];
+}
+"#
+        );
+        assert_eq!(dag.find(&Node::parse("AB&").unwrap()), None);
+    }
+
+    #[test]
+    fn quoted_name() {
+        let dot = Node::parse("A").unwrap().to_dot(&DotOptions {
+            name: r#"2 "my-graph" \ A"#,
+            ..Default::default()
+        });
+        assert!(dot.starts_with(r#"digraph "2 \"my-graph\" \\ A" {"#));
+    }
+}
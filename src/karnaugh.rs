@@ -0,0 +1,300 @@
+//! Karnaugh maps of truth tables, see [`TruthTable::karnaugh_map`].
+//!
+//! ```text
+//! AB\CD  00  01  11  10
+//!       +---+---+---+---+
+//!    00 | 0 | 1 | 1 | 0 |
+//!       ...
+//! ```
+use std::fmt;
+
+use crate::bool_formula_infix::Notation;
+use crate::cube::Cube;
+use crate::ex02_gray_code::gray_code;
+use crate::ex04_truth_table::TruthTable;
+
+/// Characters the grid is drawn with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Style {
+    /// `+-|`, group outlines with `=#`
+    #[default]
+    Ascii,
+    /// Box drawing characters, group outlines in heavy lines
+    Unicode,
+}
+
+/// Rendering of a [`TruthTable`] of 2 to 6 variables as a Karnaugh map.
+///
+/// The first half of the variables (rounded down) label the rows, the others the columns,
+/// both in Gray code order so that neighbouring cells differ by one variable.
+pub struct KarnaughMap<'a> {
+    table: &'a TruthTable,
+    style: Style,
+    dont_cares: &'a [u64],
+    groups: &'a [Cube],
+}
+
+impl TruthTable {
+    /// Panics unless the table has 2 to 6 variables.
+    pub fn karnaugh_map(&self) -> KarnaughMap<'_> {
+        assert!(
+            (2..=6).contains(&self.variables().len()),
+            "Karnaugh maps have 2 to 6 variables"
+        );
+        KarnaughMap {
+            table: self,
+            style: Style::Ascii,
+            dont_cares: &[],
+            groups: &[],
+        }
+    }
+}
+
+impl<'a> KarnaughMap<'a> {
+    pub fn style(mut self, style: Style) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Rows shown as `-`
+    pub fn dont_cares(mut self, dont_cares: &'a [u64]) -> Self {
+        self.dont_cares = dont_cares;
+        self
+    }
+
+    /// Cubes outlined on the map and named `a`, `b`, ... in the cells they cover,
+    /// such as the cover of a minimizer. At most 26 of them.
+    pub fn groups(mut self, groups: &'a [Cube]) -> Self {
+        assert!(groups.len() <= 26, "more than 26 groups");
+        self.groups = groups;
+        self
+    }
+
+    fn row_vars(&self) -> usize {
+        self.table.variables().len() / 2
+    }
+
+    fn col_vars(&self) -> usize {
+        self.table.variables().len() - self.row_vars()
+    }
+
+    /// Row of the table in a cell
+    fn index(&self, row: usize, col: usize) -> u64 {
+        let gray = |i: usize| gray_code(i as u32) as u64;
+        gray(row) << self.col_vars() | gray(col)
+    }
+
+    /// Groups of a cell as a bitmask, `0` outside of the map
+    fn groups_at(&self, row: isize, col: isize) -> u32 {
+        let (rows, cols) = (1 << self.row_vars(), 1 << self.col_vars());
+        if !(0..rows).contains(&row) || !(0..cols).contains(&col) {
+            return 0;
+        }
+        let index = self.index(row as usize, col as usize);
+        self.groups
+            .iter()
+            .enumerate()
+            .filter(|(_, group)| group.contains(index))
+            .fold(0, |groups, (i, _)| groups | 1 << i)
+    }
+
+    /// Whether the line between two cells outlines a group
+    fn heavy(&self, a: (isize, isize), b: (isize, isize)) -> bool {
+        self.groups_at(a.0, a.1) != self.groups_at(b.0, b.1)
+    }
+
+    fn horizontal(&self, heavy: bool) -> char {
+        match (self.style, heavy) {
+            (Style::Ascii, false) => '-',
+            (Style::Ascii, true) => '=',
+            (Style::Unicode, false) => '─',
+            (Style::Unicode, true) => '━',
+        }
+    }
+
+    fn vertical(&self, heavy: bool) -> char {
+        match (self.style, heavy) {
+            (Style::Ascii, false) => '|',
+            (Style::Ascii, true) => '#',
+            (Style::Unicode, false) => '│',
+            (Style::Unicode, true) => '┃',
+        }
+    }
+
+    /// Corner between lines, each arm is `Some(heavy)` if there is one
+    fn junction(&self, arms: [Option<bool>; 4]) -> char {
+        if self.style == Style::Ascii {
+            return '+';
+        }
+        let heavy = arms.contains(&Some(true));
+        let index = match arms.map(|arm| arm.is_some()) {
+            // up, down, left, right
+            [false, true, false, true] => 0,
+            [false, true, true, false] => 1,
+            [true, false, false, true] => 2,
+            [true, false, true, false] => 3,
+            [true, true, false, true] => 4,
+            [true, true, true, false] => 5,
+            [false, true, true, true] => 6,
+            [true, false, true, true] => 7,
+            _ => 8,
+        };
+        let glyphs = if heavy {
+            "┏┓┗┛┣┫┳┻╋"
+        } else {
+            "┌┐└┘├┤┬┴┼"
+        };
+        glyphs.chars().nth(index).unwrap()
+    }
+
+    fn cell_width(&self) -> usize {
+        let rows = 1 << self.row_vars();
+        let cols = 1 << self.col_vars();
+        let max_groups = (0..rows)
+            .flat_map(|row| (0..cols).map(move |col| (row, col)))
+            .map(|(row, col)| self.groups_at(row, col).count_ones() as usize)
+            .max()
+            .unwrap_or(0);
+        3 + max_groups
+    }
+}
+
+impl fmt::Display for KarnaughMap<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let variables = self.table.variables();
+        let (row_vars, col_vars) = variables.split_at(self.row_vars());
+        let (rows, cols) = (1isize << row_vars.len(), 1isize << col_vars.len());
+        let names = |vars: &[_]| vars.iter().map(|v| format!("{v}")).collect::<String>();
+        let corner = format!("{}\\{}", names(row_vars), names(col_vars));
+        let margin = corner.len().max(row_vars.len());
+        let width = self.cell_width();
+        let bits = |code: isize, len: usize| format!("{:0len$b}", gray_code(code as u32));
+
+        // Column labels
+        let mut labels = format!("{corner:>margin$} ");
+        for col in 0..cols {
+            labels += &format!(" {:^width$}", bits(col, col_vars.len()));
+        }
+        writeln!(f, "{}", labels.trim_end())?;
+
+        for row in 0..=rows {
+            // Line above the cells of `row`
+            write!(f, "{:margin$} ", "")?;
+            for col in 0..=cols {
+                let vertical = |row| self.heavy((row, col - 1), (row, col));
+                let horizontal = |col| self.heavy((row - 1, col), (row, col));
+                let arms = [
+                    (row > 0).then(|| vertical(row - 1)),
+                    (row < rows).then(|| vertical(row)),
+                    (col > 0).then(|| horizontal(col - 1)),
+                    (col < cols).then(|| horizontal(col)),
+                ];
+                write!(f, "{}", self.junction(arms))?;
+                if col < cols {
+                    let line = self.horizontal(horizontal(col)).to_string();
+                    write!(f, "{}", line.repeat(width))?;
+                }
+            }
+            writeln!(f)?;
+            if row == rows {
+                break;
+            }
+
+            write!(f, "{:>margin$} ", bits(row, row_vars.len()))?;
+            for col in 0..=cols {
+                let heavy = self.heavy((row, col - 1), (row, col));
+                write!(f, "{}", self.vertical(heavy))?;
+                if col == cols {
+                    break;
+                }
+                let index = self.index(row as usize, col as usize);
+                let value = if self.dont_cares.contains(&index) {
+                    '-'
+                } else {
+                    char::from(b'0' + self.table.get(index) as u8)
+                };
+                let groups = self.groups_at(row, col);
+                let names = (0..self.groups.len())
+                    .filter(|i| groups >> i & 1 == 1)
+                    .map(|i| char::from(b'a' + i as u8))
+                    .collect::<String>();
+                write!(
+                    f,
+                    " {:width$}",
+                    format!("{value}{names}"),
+                    width = width - 1
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        let notation = match self.style {
+            Style::Ascii => Notation::Ascii,
+            Style::Unicode => Notation::Unicode,
+        };
+        for (i, group) in self.groups.iter().enumerate() {
+            let term = group.to_node(variables);
+            writeln!(
+                f,
+                "{}: {}",
+                char::from(b'a' + i as u8),
+                term.infix(notation)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variable::Var;
+
+    #[test]
+    fn plain() {
+        let table = TruthTable::compute("AB^").unwrap();
+        assert_eq!(
+            table.karnaugh_map().to_string(),
+            "A\\B   0   1\n\
+             \x20   +---+---+\n\
+             \x20 0 | 0 | 1 |\n\
+             \x20   +---+---+\n\
+             \x20 1 | 1 | 0 |\n\
+             \x20   +---+---+\n"
+        );
+        let table = TruthTable::compute("ABC&|").unwrap();
+        let map = table.karnaugh_map().style(Style::Unicode).to_string();
+        assert_eq!(map.lines().next(), Some("A\\BC  00  01  11  10"));
+        assert_eq!(map.lines().nth(1), Some("     ┌───┬───┬───┬───┐"));
+        assert_eq!(map.lines().nth(2), Some("   0 │ 0 │ 0 │ 1 │ 0 │"));
+        assert_eq!(map.lines().last(), Some("     └───┴───┴───┴───┘"));
+    }
+
+    #[test]
+    fn groups() {
+        let vars = ['A', 'B', 'C', 'D'].map(Var::from).to_vec();
+        let table = TruthTable::from_minterms(vars, &[4, 8, 10, 11, 12, 15]).unwrap();
+        let minimized = table.quine_mccluskey(&[9, 14]).unwrap();
+        let map = table
+            .karnaugh_map()
+            .dont_cares(&[9, 14])
+            .groups(minimized.cover())
+            .to_string();
+        assert_eq!(
+            map,
+            "AB\\CD   00    01    11    10\n\
+             \x20     +-----+-----+-----+-----+\n\
+             \x20  00 | 0   | 0   | 0   | 0   |\n\
+             \x20     +=====+-----+-----+-----+\n\
+             \x20  01 # 1a  # 0   | 0   | 0   |\n\
+             \x20     +=====+-----+=====+=====+\n\
+             \x20  11 # 1ab # 0   # 1c  # -bc #\n\
+             \x20     +=====+-----+-----+-----+\n\
+             \x20  10 # 1b  # -   # 1c  # 1bc #\n\
+             \x20     +=====+-----+=====+=====+\n\
+             a: B & !C & !D\n\
+             b: A & !D\n\
+             c: A & C\n"
+        );
+    }
+}
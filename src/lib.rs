@@ -0,0 +1,32 @@
+pub mod bit_parallel;
+pub mod bool_formula_ast;
+pub mod bool_formula_infix;
+pub mod bytecode;
+pub mod cdcl;
+pub mod clause;
+pub mod cube;
+pub mod diagnostic;
+pub mod dimacs;
+pub mod disjunctive_normal_form;
+pub mod dpll;
+pub mod espresso;
+pub mod formula_dag;
+pub mod formula_dot;
+pub mod karnaugh;
+pub mod quine_mccluskey;
+pub mod simplify;
+pub mod tseitin;
+pub mod variable;
+
+pub mod ex00_adder;
+pub mod ex01_multiplier;
+pub mod ex02_gray_code;
+pub mod ex03_boolean_evaluation;
+pub mod ex04_truth_table;
+pub mod ex05_negation_normal_form;
+pub mod ex06_conjunctive_normal_form;
+pub mod ex07_sat;
+pub mod ex08_powerset;
+pub mod ex09_set_evaluation;
+pub mod ex10_curve;
+pub mod ex11_inverse;
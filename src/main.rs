@@ -1,17 +1,9 @@
-mod bool_formula_ast;
-
-mod ex00_adder;
-mod ex01_multiplier;
-mod ex02_gray_code;
-mod ex03_boolean_evaluation;
-mod ex04_truth_table;
-mod ex05_negation_normal_form;
-mod ex06_conjunctive_normal_form;
-mod ex07_sat;
-mod ex08_powerset;
-mod ex09_set_evaluation;
-mod ex10_curve;
-mod ex11_inverse;
+use ready_set_boole::{
+    bool_formula_ast::Node, bool_formula_infix::Notation, ex00_adder, ex01_multiplier,
+    ex02_gray_code, ex03_boolean_evaluation, ex04_truth_table, ex05_negation_normal_form,
+    ex06_conjunctive_normal_form, ex07_sat, ex08_powerset, ex09_set_evaluation, ex10_curve,
+    ex11_inverse,
+};
 
 fn main() {
     // Bitwise operations
@@ -41,6 +33,17 @@ fn main() {
         cnf_input,
         ex06_conjunctive_normal_form::conjunctive_normal_form(cnf_input)
     );
+    let infix_input = "(A & B) | !C => D";
+    println!(
+        "parse_infix({:?})={}",
+        infix_input,
+        Node::parse_infix(infix_input).unwrap()
+    );
+    println!(
+        "infix({:?})={}",
+        cnf_input,
+        Node::parse(cnf_input).unwrap().infix(Notation::Unicode)
+    );
     println!("sat('AA^')={}", ex07_sat::sat("AA^"));
     println!(
         "powerset(&[1, 2, 3])={:?}",
@@ -0,0 +1,283 @@
+//! Minimal sum of products of a truth table, see [`TruthTable::quine_mccluskey`].
+//!
+//! <https://en.wikipedia.org/wiki/Quine%E2%80%93McCluskey_algorithm> finds the prime implicants,
+//! <https://en.wikipedia.org/wiki/Petrick%27s_method> picks among them when the essential ones aren't enough.
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::bool_formula_ast::Node;
+use crate::cube::{Cube, sum_of_products};
+use crate::ex04_truth_table::{TruthTable, TruthTableError};
+use crate::variable::Var;
+
+/// Sum of products found by [`TruthTable::quine_mccluskey`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Minimized {
+    variables: Vec<Var>,
+    prime_implicants: Vec<Cube>,
+    essential_primes: Vec<Cube>,
+    cover: Vec<Cube>,
+}
+
+impl Minimized {
+    pub fn variables(&self) -> &[Var] {
+        &self.variables
+    }
+
+    /// Cubes of rows with result `1` or don't care that can't grow, and cover at least one `1`
+    pub fn prime_implicants(&self) -> &[Cube] {
+        &self.prime_implicants
+    }
+
+    /// Prime implicants that are the only one to cover some row
+    pub fn essential_primes(&self) -> &[Cube] {
+        &self.essential_primes
+    }
+
+    /// Fewest prime implicants covering every `1`, then fewest literals
+    pub fn cover(&self) -> &[Cube] {
+        &self.cover
+    }
+
+    /// Number of literals of the cover
+    pub fn literals(&self) -> u32 {
+        self.cover.iter().map(Cube::literals).sum()
+    }
+
+    pub fn to_node(&self) -> Node {
+        sum_of_products(&self.cover, &self.variables)
+    }
+}
+
+impl fmt::Display for Minimized {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let nb_vars = self.variables.len();
+        let mut list = |name: &str, cubes: &[Cube]| {
+            let patterns = cubes
+                .iter()
+                .map(|cube| cube.pattern(nb_vars))
+                .collect::<Vec<_>>();
+            writeln!(f, "{name}: {}", patterns.join(", "))
+        };
+        list("prime implicants", &self.prime_implicants)?;
+        list("essential primes", &self.essential_primes)?;
+        list("cover", &self.cover)
+    }
+}
+
+/// Merges cubes differing by one variable until none do, those that never merged are prime.
+fn prime_implicants(nb_vars: usize, rows: impl Iterator<Item = u64>) -> Vec<Cube> {
+    let mut cubes = rows
+        .map(|row| Cube::minterm(row, nb_vars))
+        .collect::<BTreeSet<_>>();
+    let mut primes = Vec::new();
+    while !cubes.is_empty() {
+        let mut merged = BTreeSet::new();
+        for cube in cubes.iter() {
+            let mut prime = true;
+            for bit in (0..nb_vars as u32).filter(|bit| cube.mask() >> bit & 1 == 1) {
+                let neighbour = Cube::new(cube.bits() ^ 1 << bit, cube.mask());
+                if cubes.contains(&neighbour) {
+                    merged.insert(cube.without(bit));
+                    prime = false;
+                }
+            }
+            if prime {
+                primes.push(*cube);
+            }
+        }
+        cubes = merged;
+    }
+    primes.sort();
+    primes
+}
+
+/// Smallest sets of `choices` with an element of every clause, as sorted indices.
+///
+/// Multiplies out the product of sums, exponential in the worst case.
+fn petrick(clauses: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    let mut products: Vec<Vec<usize>> = vec![vec![]];
+    for clause in clauses {
+        let mut next = Vec::new();
+        for product in products {
+            if clause.iter().any(|choice| product.contains(choice)) {
+                next.push(product);
+                continue;
+            }
+            for &choice in clause {
+                let mut product = product.clone();
+                let at = product.partition_point(|&c| c < choice);
+                product.insert(at, choice);
+                next.push(product);
+            }
+        }
+        // Absorption: `X | X & Y = X`
+        next.sort_by_key(Vec::len);
+        next.dedup();
+        products = Vec::with_capacity(next.len());
+        for product in next {
+            let absorbed = products
+                .iter()
+                .any(|kept: &Vec<usize>| kept.iter().all(|c| product.contains(c)));
+            if !absorbed {
+                products.push(product);
+            }
+        }
+    }
+    products
+}
+
+impl TruthTable {
+    /// Minimal sum of products of the table, the rows in `dont_cares` may have any result.
+    pub fn quine_mccluskey(&self, dont_cares: &[u64]) -> Result<Minimized, TruthTableError> {
+        if let Some(&row) = dont_cares.iter().find(|&&row| row >= self.rows()) {
+            return Err(TruthTableError::RowOutOfRange(row, self.rows()));
+        }
+        let nb_vars = self.variables().len();
+        let dont_cares = dont_cares.iter().copied().collect::<BTreeSet<_>>();
+        let ones = self
+            .minterms()
+            .filter(|row| !dont_cares.contains(row))
+            .collect::<Vec<_>>();
+
+        let mut prime_implicants =
+            prime_implicants(nb_vars, ones.iter().chain(dont_cares.iter()).copied());
+        prime_implicants.retain(|prime| ones.iter().any(|&row| prime.contains(row)));
+
+        // Primes covering each row
+        let chart = ones
+            .iter()
+            .map(|&row| {
+                (0..prime_implicants.len())
+                    .filter(|&i| prime_implicants[i].contains(row))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let essential = chart
+            .iter()
+            .filter(|primes| primes.len() == 1)
+            .map(|primes| primes[0])
+            .collect::<BTreeSet<_>>();
+        let remaining = chart
+            .into_iter()
+            .filter(|primes| !primes.iter().any(|i| essential.contains(i)))
+            .collect::<Vec<_>>();
+        let literals = |choice: &Vec<usize>| {
+            choice
+                .iter()
+                .map(|&i| prime_implicants[i].literals())
+                .sum::<u32>()
+        };
+        let chosen = petrick(&remaining)
+            .into_iter()
+            .min_by_key(|choice| (choice.len(), literals(choice)))
+            .unwrap_or_default();
+
+        let essential_primes = essential
+            .iter()
+            .map(|&i| prime_implicants[i])
+            .collect::<Vec<_>>();
+        let mut cover = essential_primes.clone();
+        cover.extend(chosen.into_iter().map(|i| prime_implicants[i]));
+        cover.sort();
+        Ok(Minimized {
+            variables: self.variables().to_vec(),
+            prime_implicants,
+            essential_primes,
+            cover,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dont_cares() {
+        // https://en.wikipedia.org/wiki/Quine%E2%80%93McCluskey_algorithm#Example
+        let vars = ['A', 'B', 'C', 'D'].map(Var::from).to_vec();
+        let table = TruthTable::from_minterms(vars, &[4, 8, 10, 11, 12, 15]).unwrap();
+        let minimized = table.quine_mccluskey(&[9, 14]).unwrap();
+        assert_eq!(
+            minimized.to_string(),
+            "prime implicants: -100, 1--0, 10--, 1-1-\n\
+             essential primes: -100, 1-1-\n\
+             cover: -100, 1--0, 1-1-\n"
+        );
+        assert_eq!(minimized.literals(), 7);
+        assert_eq!(minimized.to_node().to_string(), "BC!D!&&AD!&AC&||");
+        // Not row 4
+        assert!(matches!(
+            table.quine_mccluskey(&[100]),
+            Err(TruthTableError::RowOutOfRange(100, 16))
+        ));
+    }
+
+    #[test]
+    fn cyclic() {
+        // Every row is covered by two primes, none is essential
+        let vars = ['A', 'B', 'C'].map(Var::from).to_vec();
+        let table = TruthTable::from_minterms(vars, &[0, 1, 2, 5, 6, 7]).unwrap();
+        let minimized = table.quine_mccluskey(&[]).unwrap();
+        assert_eq!(minimized.prime_implicants().len(), 6);
+        assert!(minimized.essential_primes().is_empty());
+        assert_eq!(minimized.cover().len(), 3);
+        assert_eq!(minimized.literals(), 6);
+    }
+
+    #[test]
+    fn constants() {
+        let vars = vec![Var::from('A')];
+        let never = TruthTable::from_minterms(vars.clone(), &[]).unwrap();
+        assert_eq!(
+            never.quine_mccluskey(&[]).unwrap().to_node(),
+            Node::Value(false)
+        );
+        let always = TruthTable::from_minterms(vars.clone(), &[0, 1]).unwrap();
+        assert_eq!(
+            always.quine_mccluskey(&[]).unwrap().to_node(),
+            Node::Value(true)
+        );
+        let dont_care = TruthTable::from_minterms(vars, &[1]).unwrap();
+        assert_eq!(
+            dont_care.quine_mccluskey(&[0]).unwrap().to_node(),
+            Node::Value(true)
+        );
+        assert_eq!(
+            TruthTable::compute("1")
+                .unwrap()
+                .quine_mccluskey(&[])
+                .unwrap()
+                .to_node(),
+            Node::Value(true)
+        );
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..50 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D', 'E']);
+            let table = TruthTable::from_node(&tree).unwrap();
+            let dont_cares = (0..table.rows())
+                .filter(|_| rand::random::<u8>() < 32)
+                .collect::<Vec<_>>();
+            let minimized = table.quine_mccluskey(&dont_cares).unwrap();
+
+            let result = TruthTable::from_node(&minimized.to_node()).unwrap();
+            let result = result.with_variables(table.variables().to_vec()).unwrap();
+            for row in (0..table.rows()).filter(|row| !dont_cares.contains(row)) {
+                assert_eq!(result.get(row), table.get(row), "{tree}: row {row}");
+            }
+            let canonical = table.minterms().count() as u32 * table.variables().len() as u32;
+            assert!(minimized.literals() <= canonical);
+            // Every prime of the cover is needed
+            for i in 0..minimized.cover().len() {
+                let mut cover = minimized.cover().to_vec();
+                cover.remove(i);
+                assert!(table.minterms().any(|row| !dont_cares.contains(&row)
+                    && !cover.iter().any(|cube| cube.contains(row))));
+            }
+        }
+    }
+}
@@ -0,0 +1,453 @@
+//! Algebraic simplification of formulas, see [`Node::simplify`].
+use std::collections::BTreeMap;
+use std::fmt;
+use std::mem;
+
+use crate::bool_formula_ast::{Node, Op, Oper};
+
+/// Rewrite rules applied by [`Node::simplify`].
+///
+/// Every rule makes the formula smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Rule {
+    DoubleNegation,
+    NegatedConstant,
+    Idempotence,
+    Complement,
+    Identity,
+    Annihilator,
+    Absorption,
+    Xor,
+    Equivalence,
+    Implication,
+}
+
+impl Rule {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::DoubleNegation => "double negation",
+            Self::NegatedConstant => "negated constant",
+            Self::Idempotence => "idempotence",
+            Self::Complement => "complement",
+            Self::Identity => "identity",
+            Self::Annihilator => "annihilator",
+            Self::Absorption => "absorption",
+            Self::Xor => "xor",
+            Self::Equivalence => "equivalence",
+            Self::Implication => "implication",
+        }
+    }
+
+    /// The equivalences the rule applies, in infix notation.
+    pub fn laws(&self) -> &'static str {
+        match self {
+            Self::DoubleNegation => "!!A = A",
+            Self::NegatedConstant => "!0 = 1, !1 = 0",
+            Self::Idempotence => "A & A = A, A | A = A",
+            Self::Complement => "A & !A = 0, A | !A = 1",
+            Self::Identity => "A & 1 = A, A | 0 = A, A ^ 0 = A, (A = 1) = A, 1 > A = A",
+            Self::Annihilator => "A & 0 = 0, A | 1 = 1, A > 1 = 1, 0 > A = 1",
+            Self::Absorption => "A & (A | B) = A, A | (A & B) = A",
+            Self::Xor => "A ^ A = 0, A ^ !A = 1, A ^ 1 = !A",
+            Self::Equivalence => "(A = A) = 1, (A = !A) = 0, (A = 0) = !A",
+            Self::Implication => "A > A = 1, A > 0 = !A, !A > A = A, A > !A = !A",
+        }
+    }
+}
+
+impl fmt::Display for Rule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// How many times each [`Rule`] fired during [`Node::simplify`].
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Report {
+    fired: BTreeMap<Rule, usize>,
+}
+
+impl Report {
+    pub fn count(&self, rule: Rule) -> usize {
+        self.fired.get(&rule).copied().unwrap_or(0)
+    }
+
+    /// Whether the formula was already simplified.
+    pub fn is_empty(&self) -> bool {
+        self.fired.is_empty()
+    }
+
+    /// The rules that fired along with their count.
+    pub fn iter(&self) -> impl Iterator<Item = (Rule, usize)> + '_ {
+        self.fired.iter().map(|(rule, count)| (*rule, *count))
+    }
+
+    fn fire(&mut self, rule: Rule) {
+        *self.fired.entry(rule).or_default() += 1;
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (rule, count) in self.iter() {
+            writeln!(f, "{rule} ({}): {count}", rule.laws())?;
+        }
+        Ok(())
+    }
+}
+
+/// What a node is replaced with.
+enum Outcome {
+    Operand(usize),
+    NegatedOperand(usize),
+    Value(bool),
+}
+
+fn is_complement(a: &Node, b: &Node) -> bool {
+    matches!(a, Node::Neg(child) if **child == *b) || matches!(b, Node::Neg(child) if **child == *a)
+}
+
+/// Indices of `operands`, in the order of the operands.
+///
+/// Equal operands are next to each other, by increasing index.
+fn sorted_indices(operands: &[Node]) -> Vec<usize> {
+    let mut order = (0..operands.len()).collect::<Vec<_>>();
+    order.sort_by(|&a, &b| operands[a].cmp(&operands[b]).then(a.cmp(&b)));
+    order
+}
+
+/// Removes the operands of `node` marked in `removed`, `node` is replaced by its operand if only one remains.
+fn remove_operands(node: &mut Node, removed: &[bool]) {
+    let Node::Operator(Op { char, children }) = node else {
+        unreachable!();
+    };
+    let mut removed = removed.iter();
+    children.retain(|_| !removed.next().unwrap());
+    let op = *char;
+    let children = mem::take(children);
+    *node = Node::n_ary(op, children);
+}
+
+/// The operands of `node` if it's an `op`, `node` itself otherwise.
+fn operands(node: &Node, op: Oper) -> &[Node] {
+    match node {
+        Node::Operator(Op { char, children }) if *char == op => children,
+        _ => std::slice::from_ref(node),
+    }
+}
+
+/// `&` and `|`, `dominant` is the value that decides alone: `0` for `&`, `1` for `|`.
+fn simplify_lattice(node: &mut Node, op: Oper) -> Option<Rule> {
+    let Node::Operator(Op { children, .. }) = node else {
+        unreachable!();
+    };
+    let dominant = op == Oper::Disjunction;
+    let dual = if dominant {
+        Oper::Conjunction
+    } else {
+        Oper::Disjunction
+    };
+
+    if children
+        .iter()
+        .any(|c| matches!(c, Node::Value(val) if *val == dominant))
+    {
+        *node = Node::Value(dominant);
+        return Some(Rule::Annihilator);
+    }
+    if children.iter().any(|c| matches!(c, Node::Value(_))) {
+        children.retain(|c| !matches!(c, Node::Value(_)));
+        *node = if children.is_empty() {
+            Node::Value(!dominant)
+        } else {
+            Node::n_ary(op, mem::take(children))
+        };
+        return Some(Rule::Identity);
+    }
+
+    let order = sorted_indices(children);
+    let mut removed = vec![false; children.len()];
+    for pair in order.windows(2) {
+        if children[pair[0]] == children[pair[1]] {
+            removed[pair[1]] = true;
+        }
+    }
+    if removed.contains(&true) {
+        remove_operands(node, &removed);
+        return Some(Rule::Idempotence);
+    }
+
+    let has_complement = children.iter().any(|c| match c {
+        Node::Neg(child) => order.binary_search_by(|&i| children[i].cmp(child)).is_ok(),
+        _ => false,
+    });
+    if has_complement {
+        *node = Node::Value(dominant);
+        return Some(Rule::Complement);
+    }
+
+    // An operand of the dual operator is absorbed by any sibling made of some of its operands
+    for (i, child) in children.iter().enumerate() {
+        let Node::Operator(Op {
+            char,
+            children: ops,
+        }) = child
+        else {
+            continue;
+        };
+        if *char != dual {
+            continue;
+        }
+        removed[i] = children.iter().enumerate().any(|(j, sibling)| {
+            // Siblings with the same operands in another order absorb each other, only remove one
+            i != j && !removed[j] && operands(sibling, dual).iter().all(|o| ops.contains(o))
+        });
+    }
+    if removed.contains(&true) {
+        remove_operands(node, &removed);
+        return Some(Rule::Absorption);
+    }
+    None
+}
+
+fn simplify_xor(node: &mut Node) -> Option<Rule> {
+    let Node::Operator(Op { children, .. }) = node else {
+        unreachable!();
+    };
+
+    if children.iter().any(|c| matches!(c, Node::Value(_))) {
+        let ones = children
+            .iter()
+            .filter(|c| matches!(c, Node::Value(true)))
+            .count();
+        children.retain(|c| !matches!(c, Node::Value(_)));
+        let parity = ones % 2 == 1;
+        *node = if children.is_empty() {
+            Node::Value(parity)
+        } else {
+            let xor = Node::n_ary(Oper::ExclusiveDisjunction, mem::take(children));
+            if parity {
+                Node::Neg(Box::new(xor))
+            } else {
+                xor
+            }
+        };
+        return Some(if ones > 0 { Rule::Xor } else { Rule::Identity });
+    }
+
+    // Equal operands cancel out by pairs
+    let order = sorted_indices(children);
+    let mut removed = vec![false; children.len()];
+    let mut i = 0;
+    while i + 1 < order.len() {
+        if children[order[i]] == children[order[i + 1]] {
+            removed[order[i]] = true;
+            removed[order[i + 1]] = true;
+            i += 2;
+        } else {
+            i += 1;
+        }
+    }
+    if removed.iter().all(|r| *r) {
+        *node = Node::Value(false);
+        return Some(Rule::Xor);
+    }
+    if removed.contains(&true) {
+        remove_operands(node, &removed);
+        return Some(Rule::Xor);
+    }
+
+    // A complementary pair is replaced by 1, which then negates the rest
+    for (i, child) in children.iter().enumerate() {
+        let Node::Neg(grand_child) = child else {
+            continue;
+        };
+        if let Ok(j) = order.binary_search_by(|&j| children[j].cmp(grand_child)) {
+            let j = order[j];
+            children[i] = Node::Value(true);
+            children.remove(j);
+            return Some(Rule::Xor);
+        }
+    }
+    None
+}
+
+fn simplify_binary(node: &mut Node, op: Oper) -> Option<Rule> {
+    let Node::Operator(Op { children, .. }) = node else {
+        unreachable!();
+    };
+    let [left, right] = &children[..] else {
+        unreachable!();
+    };
+
+    let (rule, outcome) = match op {
+        Oper::MaterialCondition => match (left, right) {
+            (Node::Value(true), _) => (Rule::Identity, Outcome::Operand(1)),
+            (Node::Value(false), _) | (_, Node::Value(true)) => {
+                (Rule::Annihilator, Outcome::Value(true))
+            }
+            (_, Node::Value(false)) => (Rule::Implication, Outcome::NegatedOperand(0)),
+            _ if left == right => (Rule::Implication, Outcome::Value(true)),
+            _ if is_complement(left, right) => (Rule::Implication, Outcome::Operand(1)),
+            _ => return None,
+        },
+        Oper::Equivalence => match (left, right) {
+            (Node::Value(true), _) => (Rule::Identity, Outcome::Operand(1)),
+            (_, Node::Value(true)) => (Rule::Identity, Outcome::Operand(0)),
+            (Node::Value(false), _) => (Rule::Equivalence, Outcome::NegatedOperand(1)),
+            (_, Node::Value(false)) => (Rule::Equivalence, Outcome::NegatedOperand(0)),
+            _ if left == right => (Rule::Equivalence, Outcome::Value(true)),
+            _ if is_complement(left, right) => (Rule::Equivalence, Outcome::Value(false)),
+            _ => return None,
+        },
+        _ => unreachable!(),
+    };
+    *node = match outcome {
+        Outcome::Operand(i) => mem::take(&mut children[i]),
+        Outcome::NegatedOperand(i) => Node::Neg(Box::new(mem::take(&mut children[i]))),
+        Outcome::Value(val) => Node::Value(val),
+    };
+    Some(rule)
+}
+
+/// Applies the first rule that matches `node`, its children are already simplified.
+fn simplify_node(node: &mut Node) -> Option<Rule> {
+    match node {
+        Node::Value(_) | Node::Variable(_) => None,
+        Node::Neg(child) => {
+            let (rule, new) = match &mut **child {
+                Node::Value(val) => (Rule::NegatedConstant, Node::Value(!*val)),
+                Node::Neg(grand_child) => (Rule::DoubleNegation, mem::take(&mut **grand_child)),
+                Node::Variable(_) | Node::Operator(_) => return None,
+            };
+            *node = new;
+            Some(rule)
+        }
+        Node::Operator(Op { char, .. }) => match *char {
+            op @ (Oper::Conjunction | Oper::Disjunction) => simplify_lattice(node, op),
+            Oper::ExclusiveDisjunction => simplify_xor(node),
+            op @ (Oper::MaterialCondition | Oper::Equivalence) => simplify_binary(node, op),
+        },
+    }
+}
+
+impl Node {
+    /// Applies every [`Rule`] until none matches and reports how many times each one fired.
+    ///
+    /// The n-ary operators are flattened, but the order of their operands is kept.
+    pub fn simplify(&mut self) -> Report {
+        let mut report = Report::default();
+        loop {
+            // Rules can leave an operator as the single operand of the same operator
+            self.flatten_mut();
+            let mut fired = false;
+            self.transform_bottom_up(|node| {
+                while let Some(rule) = simplify_node(node) {
+                    report.fire(rule);
+                    fired = true;
+                }
+            });
+            if !fired {
+                return report;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ex04_truth_table::TruthTable;
+
+    fn simplify(formula: &str) -> (String, Report) {
+        let mut tree = Node::parse(formula).unwrap();
+        let report = tree.simplify();
+        (tree.to_string(), report)
+    }
+
+    #[track_caller]
+    fn assert_simplifies(formula: &str, expected: &str, rule: Rule) {
+        let (simplified, report) = simplify(formula);
+        assert_eq!(simplified, expected, "{formula}");
+        assert!(report.count(rule) > 0, "{formula}: {report:?}");
+    }
+
+    #[test]
+    fn rules() {
+        // The parser already removes double negations
+        let mut tree = Node::Neg(Box::new(Node::parse("A!").unwrap()));
+        assert_eq!(tree.simplify().count(Rule::DoubleNegation), 1);
+        assert_eq!(tree.to_string(), "A");
+        assert_simplifies("A0!&", "A", Rule::NegatedConstant);
+        assert_simplifies("AA&", "A", Rule::Idempotence);
+        assert_simplifies("ABA||", "AB|", Rule::Idempotence);
+        assert_simplifies("AA!|", "1", Rule::Complement);
+        assert_simplifies("BA!A&&", "0", Rule::Complement);
+        assert_simplifies("A1&", "A", Rule::Identity);
+        assert_simplifies("0A|", "A", Rule::Identity);
+        assert_simplifies("A0&B|", "B", Rule::Annihilator);
+        assert_simplifies("AB&A|", "A", Rule::Absorption);
+        assert_simplifies("AB|BCA||&", "AB|", Rule::Absorption);
+        assert_simplifies("AB|BA|&", "BA|", Rule::Absorption);
+        assert_simplifies("ABA^^", "B", Rule::Xor);
+        assert_simplifies("AA!^", "1", Rule::Xor);
+        assert_simplifies("AB^1^", "AB^!", Rule::Xor);
+        assert_simplifies("AB^B!^", "A!", Rule::Xor);
+        assert_simplifies("AA=", "1", Rule::Equivalence);
+        assert_simplifies("AA!=", "0", Rule::Equivalence);
+        assert_simplifies("A0=", "A!", Rule::Equivalence);
+        assert_simplifies("1A=", "A", Rule::Identity);
+        assert_simplifies("AA>", "1", Rule::Implication);
+        assert_simplifies("A!A>", "A", Rule::Implication);
+        assert_simplifies("AA!>", "A!", Rule::Implication);
+        assert_simplifies("A0>", "A!", Rule::Implication);
+        assert_simplifies("0A>", "1", Rule::Annihilator);
+    }
+
+    #[test]
+    fn report() {
+        let (simplified, report) = simplify("AA&B1&|");
+        assert_eq!(simplified, "AB|");
+        assert_eq!(report.count(Rule::Idempotence), 1);
+        assert_eq!(report.count(Rule::Identity), 1);
+        assert_eq!(report.count(Rule::Absorption), 0);
+        assert_eq!(
+            report.to_string(),
+            "idempotence (A & A = A, A | A = A): 1\n\
+             identity (A & 1 = A, A | 0 = A, A ^ 0 = A, (A = 1) = A, 1 > A = A): 1\n"
+        );
+        assert!(simplify("AB&C|").1.is_empty());
+        assert!(simplify("AB|CA|&").1.is_empty());
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..500 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D']);
+            let mut simplified = tree.clone();
+            simplified.simplify();
+            println!("{tree} -> {simplified}");
+
+            let truth_table = TruthTable::compute(&tree.to_string()).unwrap();
+            assert_eq!(
+                truth_table.to_string(),
+                TruthTable::compute_with_given_vars(
+                    &simplified.to_string(),
+                    truth_table.variables().to_vec()
+                )
+                .unwrap()
+                .to_string()
+            );
+            assert!(simplified.to_string().len() <= tree.to_string().len());
+            assert!(simplified.simplify().is_empty(), "not a fixpoint");
+        }
+    }
+
+    #[test]
+    fn huge_formula() {
+        let mut tree = Node::parse(format!("A{}", "B&".repeat(1_000_000))).unwrap();
+        let report = tree.simplify();
+        assert_eq!(tree.to_string(), "AB&");
+        assert_eq!(report.count(Rule::Idempotence), 1);
+    }
+}
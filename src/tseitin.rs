@@ -0,0 +1,325 @@
+//! Equisatisfiable CNF of linear size, see [`Node::tseitin`].
+//!
+//! <https://en.wikipedia.org/wiki/Tseytin_transformation>: every operator of the formula gets
+//! a fresh variable and a few clauses tying it to its operands, instead of distributing
+//! `|` over `&` like [`Node::to_conjunctive_normal_form_mut`].
+use std::collections::HashSet;
+
+use crate::bool_formula_ast::{Node, Oper};
+use crate::formula_dag::{Dag, DagNode, NodeId};
+use crate::variable::Var;
+
+/// Which implications between an auxiliary variable and its subformula are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Polarity {
+    /// `t = sub`, the models of the CNF are those of the formula extended with the auxiliaries
+    #[default]
+    Both,
+    /// Plaisted–Greenbaum: only the directions the polarity of `sub` in the formula needs,
+    /// fewer clauses but the auxiliaries of a model may differ from their subformula
+    Needed,
+}
+
+const POSITIVE: u8 = 1;
+const NEGATIVE: u8 = 2;
+
+/// A variable or its negation
+type Literal = (Var, bool);
+
+/// CNF made by [`Node::tseitin`] and its auxiliary variables.
+#[derive(Debug)]
+pub struct Encoding {
+    cnf: Node,
+    dag: Dag,
+    auxiliaries: Vec<(Var, NodeId)>,
+}
+
+impl Encoding {
+    /// `&` of `|` of literals, satisfiable if and only if the formula is.
+    pub fn cnf(&self) -> &Node {
+        &self.cnf
+    }
+
+    pub fn into_cnf(self) -> Node {
+        self.cnf
+    }
+
+    /// Auxiliary variables and the subformulas they stand for, innermost first.
+    ///
+    /// Subformulas are expanded back into trees, which can be much bigger than the CNF.
+    pub fn auxiliaries(&self) -> impl Iterator<Item = (Var, Node)> + '_ {
+        self.auxiliaries
+            .iter()
+            .map(|&(var, id)| (var, self.dag.to_node(id)))
+    }
+
+    /// Subformula `var` stands for, `None` if it isn't an auxiliary variable
+    pub fn definition(&self, var: Var) -> Option<Node> {
+        let &(_, id) = self.auxiliaries.iter().find(|(aux, _)| *aux == var)?;
+        Some(self.dag.to_node(id))
+    }
+}
+
+/// Fresh variable names `_t0`, `_t1`, ... that aren't used by the formula
+struct FreshVars {
+    used: HashSet<Var>,
+    next: usize,
+}
+
+impl Iterator for FreshVars {
+    type Item = Var;
+
+    fn next(&mut self) -> Option<Var> {
+        loop {
+            let var = Var::new(&format!("_t{}", self.next));
+            self.next += 1;
+            if !self.used.contains(&var) {
+                return Some(var);
+            }
+        }
+    }
+}
+
+fn literal_node((var, positive): Literal) -> Node {
+    if positive {
+        Node::Variable(var)
+    } else {
+        Node::Neg(Box::new(Node::Variable(var)))
+    }
+}
+
+fn not((var, positive): Literal) -> Literal {
+    (var, !positive)
+}
+
+impl Node {
+    /// Equisatisfiable CNF with an auxiliary variable per distinct operator of `self`,
+    /// its size is linear in the size of `self`.
+    pub fn tseitin(&self, polarity: Polarity) -> Encoding {
+        let mut dag = Dag::new();
+        let root = dag.insert(self);
+        let mut fresh = FreshVars {
+            used: self.variables().into_iter().collect(),
+            next: 0,
+        };
+
+        // Every node of the arena is in the formula, children come before their parents
+        let ids = dag.node_ids().collect::<Vec<_>>();
+        let nb_nodes = ids.len();
+        let mut polarities = vec![0u8; nb_nodes];
+        polarities[root.index()] = POSITIVE;
+        for i in (0..nb_nodes).rev() {
+            let both = POSITIVE | NEGATIVE;
+            let flip = |p: u8| (p & POSITIVE) << 1 | (p & NEGATIVE) >> 1;
+            let p = match polarity {
+                Polarity::Both => both,
+                Polarity::Needed => polarities[i],
+            };
+            let mut mark = |child: NodeId, p: u8| polarities[child.index()] |= p;
+            match dag.get(ids[i]) {
+                DagNode::Value(_) | DagNode::Variable(_) => (),
+                DagNode::Neg(child) => mark(child, flip(p)),
+                DagNode::Operator(Oper::Conjunction | Oper::Disjunction, [a, b]) => {
+                    mark(a, p);
+                    mark(b, p);
+                }
+                DagNode::Operator(Oper::MaterialCondition, [a, b]) => {
+                    mark(a, flip(p));
+                    mark(b, p);
+                }
+                DagNode::Operator(Oper::ExclusiveDisjunction | Oper::Equivalence, [a, b]) => {
+                    mark(a, both);
+                    mark(b, both);
+                }
+            }
+        }
+
+        let mut literals: Vec<Literal> = Vec::with_capacity(nb_nodes);
+        let mut clauses: Vec<Vec<Literal>> = Vec::new();
+        let mut auxiliaries = Vec::new();
+        for i in 0..nb_nodes {
+            let p = match polarity {
+                Polarity::Both => POSITIVE | NEGATIVE,
+                Polarity::Needed => polarities[i],
+            };
+            let literal = match dag.get(ids[i]) {
+                DagNode::Variable(var) => (var, true),
+                DagNode::Neg(child) => not(literals[child.index()]),
+                node => {
+                    let x = (fresh.next().unwrap(), true);
+                    auxiliaries.push((x.0, ids[i]));
+                    let mut clause = |needed: u8, clause: &[Literal]| {
+                        if p & needed != 0 {
+                            clauses.push(clause.to_vec());
+                        }
+                    };
+                    let (pos, neg) = (POSITIVE, NEGATIVE);
+                    match node {
+                        DagNode::Value(true) => clause(pos | neg, &[x]),
+                        DagNode::Value(false) => clause(pos | neg, &[not(x)]),
+                        DagNode::Operator(op, [a, b]) => {
+                            let (a, b) = (literals[a.index()], literals[b.index()]);
+                            match op {
+                                Oper::Conjunction => {
+                                    clause(pos, &[not(x), a]);
+                                    clause(pos, &[not(x), b]);
+                                    clause(neg, &[not(a), not(b), x]);
+                                }
+                                Oper::Disjunction => {
+                                    clause(pos, &[not(x), a, b]);
+                                    clause(neg, &[not(a), x]);
+                                    clause(neg, &[not(b), x]);
+                                }
+                                Oper::ExclusiveDisjunction => {
+                                    clause(pos, &[not(x), a, b]);
+                                    clause(pos, &[not(x), not(a), not(b)]);
+                                    clause(neg, &[not(a), b, x]);
+                                    clause(neg, &[a, not(b), x]);
+                                }
+                                Oper::Equivalence => {
+                                    clause(pos, &[not(x), not(a), b]);
+                                    clause(pos, &[not(x), a, not(b)]);
+                                    clause(neg, &[a, b, x]);
+                                    clause(neg, &[not(a), not(b), x]);
+                                }
+                                Oper::MaterialCondition => {
+                                    clause(pos, &[not(x), not(a), b]);
+                                    clause(neg, &[a, x]);
+                                    clause(neg, &[not(b), x]);
+                                }
+                            }
+                        }
+                        DagNode::Variable(_) | DagNode::Neg(_) => unreachable!(),
+                    }
+                    x
+                }
+            };
+            literals.push(literal);
+        }
+        clauses.push(vec![literals[root.index()]]);
+
+        let clauses = clauses
+            .into_iter()
+            .map(|clause| {
+                let literals = clause.into_iter().map(literal_node).collect();
+                Node::n_ary(Oper::Disjunction, literals)
+            })
+            .collect();
+        Encoding {
+            cnf: Node::n_ary(Oper::Conjunction, clauses),
+            dag,
+            auxiliaries,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bit_parallel::{count_models, satisfiable};
+    use std::collections::HashMap;
+
+    #[test]
+    fn encoding() {
+        let tree = Node::parse("AB&AB&C|&").unwrap();
+        let encoding = tree.tseitin(Polarity::Both);
+        // `AB&` is shared
+        let auxiliaries = encoding.auxiliaries().collect::<Vec<_>>();
+        assert_eq!(auxiliaries.len(), 3);
+        assert_eq!(
+            auxiliaries[0],
+            (Var::new("_t0"), Node::parse("AB&").unwrap())
+        );
+        assert_eq!(
+            encoding.definition(Var::new("_t1")),
+            Node::parse("AB&C|").ok()
+        );
+        assert_eq!(encoding.definition(Var::from('A')), None);
+        assert_eq!(
+            encoding.cnf().to_string(),
+            "[_t0]!A|[_t0]!B|A!B![_t0]||[_t1]![_t0]C||[_t0]![_t1]|C![_t1]|\
+             [_t2]![_t0]|[_t2]![_t1]|[_t0]![_t1]![_t2]||[_t2]&&&&&&&&&"
+        );
+        assert!(encoding.cnf().clone().is_conjunctive_normal_form(true));
+
+        let needed = tree.tseitin(Polarity::Needed);
+        assert_eq!(
+            needed.cnf().to_string(),
+            "[_t0]!A|[_t0]!B|[_t1]![_t0]C||[_t2]![_t0]|[_t2]![_t1]|[_t2]&&&&&"
+        );
+    }
+
+    #[test]
+    fn fresh_variables() {
+        let tree = Node::parse("[_t0][_t1]|[_t2]&").unwrap();
+        let encoding = tree.tseitin(Polarity::Both);
+        let auxiliaries = encoding
+            .auxiliaries()
+            .map(|(var, _)| var)
+            .collect::<Vec<_>>();
+        assert_eq!(auxiliaries, [Var::new("_t3"), Var::new("_t4")]);
+
+        let constant = Node::parse("0").unwrap().tseitin(Polarity::Both);
+        assert_eq!(constant.cnf().to_string(), "[_t0]![_t0]&");
+    }
+
+    #[test]
+    fn smoke_test_random() {
+        for _ in 0..100 {
+            let tree = Node::new_random(&['A', 'B', 'C', 'D']);
+            if tree.to_string().len() > 25 {
+                // That's a tad big, the CNF gets a variable per operator
+                continue;
+            }
+            let variables = tree.variables();
+            for polarity in [Polarity::Both, Polarity::Needed] {
+                let encoding = tree.tseitin(polarity);
+                let cnf = encoding.cnf();
+                assert!(cnf.clone().is_conjunctive_normal_form(true));
+                assert_eq!(satisfiable(cnf), satisfiable(&tree), "{tree}");
+
+                // Models of the formula extended with the auxiliaries satisfy the CNF
+                let auxiliaries = encoding.auxiliaries().collect::<Vec<_>>();
+                for row in 0..1u64 << variables.len() {
+                    let mut assignment = variables
+                        .iter()
+                        .enumerate()
+                        .map(|(i, &var)| (var, row >> (variables.len() - 1 - i) & 1 == 1))
+                        .collect::<HashMap<_, _>>();
+                    for (var, definition) in auxiliaries.iter() {
+                        let value = definition.evaluate(&assignment).unwrap();
+                        assignment.insert(*var, value);
+                    }
+                    let expected = tree.evaluate(&assignment).unwrap();
+                    let satisfied = cnf.evaluate(&assignment).unwrap();
+                    match polarity {
+                        Polarity::Both => assert_eq!(satisfied, expected, "{tree}"),
+                        Polarity::Needed => assert!(satisfied || !expected, "{tree}"),
+                    }
+                }
+
+                // Models of the CNF satisfy the formula
+                let mut counter_example = tree.clone();
+                counter_example.neg();
+                let both = Node::n_ary(Oper::Conjunction, vec![cnf.clone(), counter_example]);
+                assert!(!satisfiable(&both), "{tree}");
+            }
+            // One model of the CNF per model of the formula
+            let encoding = tree.tseitin(Polarity::Both);
+            let extra = encoding.cnf().variables().len() - variables.len();
+            let models = count_models(encoding.cnf());
+            let formula_models = count_models(&tree) << (extra - encoding.auxiliaries.len());
+            assert_eq!(models, formula_models, "{tree}");
+        }
+    }
+
+    #[test]
+    fn huge_formula() {
+        let formula = format!("A{}", "B^".repeat(100_000));
+        let tree = Node::parse(formula.as_str()).unwrap();
+        let encoding = tree.tseitin(Polarity::Needed);
+        assert_eq!(encoding.auxiliaries.len(), 100_000);
+        // 4 clauses per `^`, but the root one is only needed positively
+        assert_eq!(encoding.cnf().children().len(), 4 * 100_000 - 2 + 1);
+    }
+}
@@ -0,0 +1,183 @@
+//! Variable names.
+//!
+//! Names are interned once for the whole program, so [`Var`] is a `Copy` handle
+//! that compares and hashes as fast as a `char`.
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+const LETTERS: [&str; 26] = [
+    "A", "B", "C", "D", "E", "F", "G", "H", "I", "J", "K", "L", "M", "N", "O", "P", "Q", "R", "S",
+    "T", "U", "V", "W", "X", "Y", "Z",
+];
+
+/// Every name that isn't a single uppercase letter, those live in `LETTERS`.
+static INTERNER: Mutex<BTreeSet<&'static str>> = Mutex::new(BTreeSet::new());
+
+/// Variable of a formula: `A` to `Z` or any identifier such as `x12` or `carry_3`.
+///
+/// Variables are ordered by name, with digit runs compared as numbers (`x2` < `x10`).
+#[derive(Clone, Copy)]
+pub struct Var(&'static str);
+
+impl Var {
+    /// Returns the variable named `name`.
+    ///
+    /// # Panics
+    ///
+    /// If `name` is not a valid identifier, see [`Var::is_valid_name`].
+    pub fn new(name: &str) -> Self {
+        assert!(Self::is_valid_name(name), "invalid variable name: {name:?}");
+        if let [letter @ b'A'..=b'Z'] = name.as_bytes() {
+            return Self(LETTERS[(letter - b'A') as usize]);
+        }
+
+        let mut interner = INTERNER.lock().unwrap();
+        match interner.get(name) {
+            Some(interned) => Self(interned),
+            None => {
+                let interned: &'static str = Box::leak(name.into());
+                interner.insert(interned);
+                Self(interned)
+            }
+        }
+    }
+
+    /// `[A-Za-z_][A-Za-z0-9_]*`
+    pub fn is_valid_name(name: &str) -> bool {
+        let mut chars = name.chars();
+        chars
+            .next()
+            .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    /// The letter of single uppercase letter variables, which RPN writes without brackets.
+    pub fn as_letter(&self) -> Option<char> {
+        match self.0.as_bytes() {
+            [letter @ b'A'..=b'Z'] => Some(*letter as char),
+            _ => None,
+        }
+    }
+}
+
+impl From<char> for Var {
+    fn from(letter: char) -> Self {
+        Self::new(letter.encode_utf8(&mut [0; 4]))
+    }
+}
+
+impl PartialEq for Var {
+    fn eq(&self, other: &Self) -> bool {
+        // Interned, the same name always has the same address
+        std::ptr::eq(self.0, other.0)
+    }
+}
+
+impl Eq for Var {}
+
+impl Hash for Var {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.as_ptr().hash(state);
+    }
+}
+
+impl Ord for Var {
+    fn cmp(&self, other: &Self) -> Ordering {
+        if self == other {
+            return Ordering::Equal;
+        }
+        natural_cmp(self.0, other.0).then_with(|| self.0.cmp(other.0))
+    }
+}
+
+impl PartialOrd for Var {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Compares digit runs by numeric value and everything else character by character.
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut a, mut b) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (a.first(), b.first()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let digits = |s: &[u8]| s.iter().take_while(|c| c.is_ascii_digit()).count();
+                let (len_a, len_b) = (digits(a), digits(b));
+                let (num_a, num_b) = (trim_zeros(&a[..len_a]), trim_zeros(&b[..len_b]));
+                let ord = num_a.len().cmp(&num_b.len()).then(num_a.cmp(num_b));
+                if ord != Ordering::Equal {
+                    return ord;
+                }
+                (a, b) = (&a[len_a..], &b[len_b..]);
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(y);
+                }
+                (a, b) = (&a[1..], &b[1..]);
+            }
+        }
+    }
+}
+
+fn trim_zeros(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|&&c| c == b'0').count();
+    &digits[zeros..]
+}
+
+impl fmt::Display for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+impl fmt::Debug for Var {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Var({:?})", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn interning() {
+        assert_eq!(Var::new("x12"), Var::new(&String::from("x12")));
+        assert_eq!(Var::new("A"), Var::from('A'));
+        assert_ne!(Var::new("A"), Var::new("a"));
+        assert_eq!(Var::new("carry_3").name(), "carry_3");
+        assert_eq!(Var::from('Z').as_letter(), Some('Z'));
+        assert_eq!(Var::new("AB").as_letter(), None);
+    }
+
+    #[test]
+    fn valid_names() {
+        assert!(Var::is_valid_name("_"));
+        assert!(Var::is_valid_name("x_1"));
+        assert!(!Var::is_valid_name(""));
+        assert!(!Var::is_valid_name("1x"));
+        assert!(!Var::is_valid_name("x-1"));
+    }
+
+    #[test]
+    fn natural_order() {
+        let mut vars = ["x10", "x2", "B", "x02", "A", "x1", "a"].map(Var::new);
+        vars.sort();
+        assert_eq!(
+            vars.map(|v| v.name()),
+            ["A", "B", "a", "x1", "x02", "x2", "x10"]
+        );
+    }
+}